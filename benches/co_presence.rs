@@ -0,0 +1,174 @@
+//! Compares building the owner's 2-hop graph with one query per location and user against loading
+//! the co-presence matrix in a single pass.
+//!
+//! Runs against a synthetic database, `cargo bench --bench co_presence`.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::types::chrono::{TimeZone, Utc};
+use sqlx::SqlitePool;
+
+use vrcx_insights::zaphkiel::co_presence::CoPresence;
+use vrcx_insights::zaphkiel::db::{establish_connection, DbConfig};
+use vrcx_insights::zaphkiel::error::Result;
+use vrcx_insights::zaphkiel::ids::Id;
+use vrcx_insights::zaphkiel::query_options::QueryOptions;
+use vrcx_insights::{get_co_presence_for, get_co_presence_matrix, get_locations_for};
+
+const USERS: u64 = 200;
+const GROUP_SIZE: u64 = 8;
+const VISITS: u64 = 1000;
+const WORLDS: u64 = 50;
+
+/// Small deterministic generator, good enough for synthetic data.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: u64) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (self.0 >> 33) % bound
+    }
+}
+
+fn user_id(user: u64) -> String {
+    format!("usr_{user:08}-0000-0000-0000-000000000000")
+}
+
+async fn create_database(path: &PathBuf) -> Result<()> {
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let pool = SqlitePool::connect_with(
+        SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true),
+    )
+    .await?;
+    sqlx::query(
+        "create table gamelog_join_leave (id INTEGER primary key, created_at TEXT, type TEXT,
+        display_name TEXT, location TEXT, user_id TEXT, time INTEGER)",
+    )
+    .execute(&pool)
+    .await?;
+
+    let mut rng = Lcg(42);
+    let start = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+    let mut tx = pool.begin().await?;
+    for visit in 0..VISITS {
+        let location = format!("wrld_{:04}:{visit}~region(eu)", rng.next(WORLDS));
+        let at = start + chrono::Duration::minutes(i64::try_from(visit * 30).unwrap());
+        let group = rng.next(USERS / GROUP_SIZE) * GROUP_SIZE;
+        let strangers = (0..rng.next(10))
+            .map(|_| rng.next(USERS))
+            .collect::<Vec<_>>();
+        for user in (group..group + GROUP_SIZE).chain(strangers) {
+            let joined = at + chrono::Duration::seconds(i64::try_from(rng.next(600)).unwrap());
+            let stay = rng.next(7200) + 60;
+            let left = joined + chrono::Duration::seconds(i64::try_from(stay).unwrap());
+            for (created_at, event, time) in [
+                (joined, "OnPlayerJoined", 0),
+                (left, "OnPlayerLeft", stay * 1000),
+            ] {
+                sqlx::query(
+                    "insert into gamelog_join_leave
+                    (created_at, type, display_name, location, user_id, time)
+                    values (?, ?, ?, ?, ?, ?)",
+                )
+                .bind(created_at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+                .bind(event)
+                .bind(format!("User{user}"))
+                .bind(&location)
+                .bind(user_id(user))
+                .bind(i64::try_from(time).unwrap())
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+    }
+    tx.commit().await?;
+    pool.close().await;
+
+    Ok(())
+}
+
+async fn per_location(
+    owner: &Id,
+    conn: &Arc<SqlitePool>,
+    options: &QueryOptions,
+) -> Result<HashMap<Id, HashMap<Id, CoPresence>>> {
+    let others_of = |user_id: Id| async move {
+        let locations = get_locations_for(user_id.clone(), conn.clone(), options).await?;
+        get_co_presence_for(user_id, conn.clone(), locations, options).await
+    };
+
+    let mut graph = HashMap::new();
+    let owner_others = others_of(owner.clone()).await?;
+    for other in owner_others.keys() {
+        graph.insert(other.clone(), others_of(other.clone()).await?);
+    }
+    graph.insert(owner.clone(), owner_others);
+    Ok(graph)
+}
+
+async fn single_pass(
+    owner: &Id,
+    conn: &Arc<SqlitePool>,
+    options: &QueryOptions,
+) -> Result<HashMap<Id, HashMap<Id, CoPresence>>> {
+    let matrix = get_co_presence_matrix(conn.clone(), options).await?;
+
+    let mut graph = HashMap::new();
+    let owner_others = matrix.others_of(owner);
+    for other in owner_others.keys() {
+        graph.insert(other.clone(), matrix.others_of(other));
+    }
+    graph.insert(owner.clone(), owner_others);
+    Ok(graph)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let path = std::env::temp_dir().join("vrcx-insights-bench.sqlite3");
+    create_database(&path).await?;
+
+    let conn = Arc::new(establish_connection(&DbConfig::new(path.clone())).await?);
+    let options = QueryOptions::default();
+    let owner: Id = user_id(0).into();
+
+    let start = Instant::now();
+    let old = per_location(&owner, &conn, &options).await?;
+    let per_location_time = start.elapsed();
+
+    let start = Instant::now();
+    let new = single_pass(&owner, &conn, &options).await?;
+    let single_pass_time = start.elapsed();
+
+    assert_eq!(
+        old[&owner], new[&owner],
+        "the owner's neighbourhood differs"
+    );
+
+    println!(
+        "{USERS} users, {VISITS} instances, 2-hop graph of {} users",
+        new.len()
+    );
+    println!("    per location => {per_location_time:?}");
+    println!("    single pass  => {single_pass_time:?}");
+    println!(
+        "    speedup      => {:.1}x",
+        per_location_time.as_secs_f64()
+            / single_pass_time.max(Duration::from_nanos(1)).as_secs_f64()
+    );
+
+    conn.close().await;
+    std::fs::remove_file(path)?;
+
+    Ok(())
+}
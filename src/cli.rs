@@ -0,0 +1,508 @@
+use std::path::PathBuf;
+
+use chrono::{Utc, Weekday};
+use chrono_tz::Tz;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use vrcx_insights::zaphkiel::community::DEFAULT_RESOLUTION;
+use vrcx_insights::zaphkiel::config::Config;
+use vrcx_insights::zaphkiel::cores::CoreOptions;
+use vrcx_insights::zaphkiel::db::{DbConfig, DbMode};
+use vrcx_insights::zaphkiel::error::{Error, Result};
+use vrcx_insights::zaphkiel::friendship::Classifier;
+use vrcx_insights::zaphkiel::ids::Id;
+use vrcx_insights::zaphkiel::query_options::OnMalformed;
+use vrcx_insights::zaphkiel::suspects::DetectionOptions;
+use vrcx_insights::zaphkiel::time_filter::{parse_hours, parse_instant, TimeFilter};
+use vrcx_insights::zaphkiel::timeline::{Period, TimelineOptions};
+use vrcx_insights::zaphkiel::weight::WeightMode;
+
+/// Find out the friend circles hiding in a VRCX database.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Config file, defaults to `vrcx-insights.ron` if it exists
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub db: DbArgs,
+
+    #[command(flatten)]
+    pub time: TimeArgs,
+
+    /// Skip and count rows that can't be parsed instead of stopping at the first one
+    #[arg(long, global = true)]
+    pub skip_malformed: bool,
+
+    /// User id of the database owner, takes precedence over `--owner-id-file`
+    #[arg(long, global = true)]
+    pub owner_id: Option<String>,
+
+    /// File containing the user id of the database owner
+    #[arg(long, global = true, default_value = "owner_id.txt")]
+    pub owner_id_file: PathBuf,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+impl Cli {
+    /// The config file given with `--config`, or the default one if there is one. Loaded once, the
+    /// flags are then layered on top of its sections.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Config`] if the config file is invalid.
+    pub fn load_config(&self) -> Result<Config> {
+        Config::load_or_default(self.config.as_deref())
+    }
+
+    /// Layer the environment and the database flags on top of the `db` section of the config file.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Config`] if an environment variable is invalid.
+    pub fn db_config(&self, config: DbConfig) -> Result<DbConfig> {
+        let mut config = config.with_env()?;
+
+        let DbArgs {
+            db,
+            db_mode,
+            pool_size,
+            acquire_timeout,
+            busy_timeout,
+        } = &self.db;
+
+        if let Some(db) = db {
+            config.path.clone_from(db);
+        }
+        if let Some(db_mode) = db_mode {
+            config.mode = *db_mode;
+        }
+        if let Some(pool_size) = pool_size {
+            config.pool_size = *pool_size;
+        }
+        if let Some(acquire_timeout) = acquire_timeout {
+            config.acquire_timeout_secs = *acquire_timeout;
+        }
+        if let Some(busy_timeout) = busy_timeout {
+            config.busy_timeout_secs = *busy_timeout;
+        }
+
+        Ok(config)
+    }
+
+    /// Layer the time flags on top of the `time` section of the config file.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Config`] if a flag is invalid.
+    pub fn time_filter(&self, mut filter: TimeFilter) -> Result<TimeFilter> {
+        let TimeArgs {
+            since,
+            until,
+            weekdays,
+            hours,
+            timezone,
+        } = &self.time;
+
+        let now = Utc::now();
+        if let Some(since) = since {
+            filter.since = Some(parse_instant(since, now)?);
+        }
+        if let Some(until) = until {
+            filter.until = Some(parse_instant(until, now)?);
+        }
+        if !weekdays.is_empty() {
+            filter.weekdays.clone_from(weekdays);
+        }
+        if let Some(hours) = hours {
+            filter.hours = Some(parse_hours(hours)?);
+        }
+        if let Some(timezone) = timezone {
+            filter.timezone = *timezone;
+        }
+
+        Ok(filter)
+    }
+
+    /// Resolve the owner id from `--owner-id` or from the contents of `--owner-id-file`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Config`] if there is no `--owner-id` and the file can't be read.
+    pub fn owner_id(&self) -> Result<Id> {
+        match &self.owner_id {
+            Some(owner_id) => Ok(owner_id.trim().into()),
+            None => std::fs::read_to_string(&self.owner_id_file)
+                .map(|it| it.trim().into())
+                .map_err(|err| {
+                    Error::Config(format!(
+                        "could not read owner id from {}: {err}",
+                        self.owner_id_file.display()
+                    ))
+                }),
+        }
+    }
+
+    #[must_use]
+    pub const fn on_malformed(&self) -> OnMalformed {
+        if self.skip_malformed {
+            OnMalformed::Skip
+        } else {
+            OnMalformed::Abort
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct TimeArgs {
+    /// Leave out everything before: a date (`2023-01-31`, UTC), a timestamp or a number of days
+    /// ago (`90d`)
+    #[arg(long, global = true)]
+    pub since: Option<String>,
+
+    /// Leave out everything from then on, like `--since`
+    #[arg(long, global = true)]
+    pub until: Option<String>,
+
+    /// Only keep these days of the week, like `sat,sun`
+    #[arg(long, global = true, value_delimiter = ',')]
+    pub weekdays: Vec<Weekday>,
+
+    /// Only keep these hours of the day, like `20-2` for 8pm to 2am
+    #[arg(long, global = true)]
+    pub hours: Option<String>,
+
+    /// Timezone of `--weekdays` and `--hours`, like `Europe/Paris` [default: UTC]
+    #[arg(long, global = true)]
+    pub timezone: Option<Tz>,
+}
+
+#[derive(Debug, Args)]
+pub struct DbArgs {
+    /// Path to the `VRCX.sqlite3` database [default: db/VRCX.sqlite3]
+    #[arg(long, global = true)]
+    pub db: Option<PathBuf>,
+
+    /// How to open the database: `read-only` or `immutable` (no locking, only for copies)
+    #[arg(long, global = true)]
+    pub db_mode: Option<DbMode>,
+
+    /// Maximum number of database connections
+    #[arg(long, global = true)]
+    pub pool_size: Option<u32>,
+
+    /// Seconds to wait for a free connection from the pool
+    #[arg(long, global = true)]
+    pub acquire_timeout: Option<u64>,
+
+    /// Seconds to wait for a lock held by a running VRCX before giving up
+    #[arg(long, global = true)]
+    pub busy_timeout: Option<u64>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Build the friend graph for the owner, print a summary and write every output
+    Analyze(AnalyzeArgs),
+    /// Build the friend graph for the owner and only write the selected outputs
+    Export(ExportArgs),
+    /// Show who a single user spends their time with
+    InspectUser(InspectUserArgs),
+    /// List the worlds a user has been seen in
+    Worlds(WorldsArgs),
+    /// Compare the inferred friends of the owner with their friend list in VRCX
+    Validate(ValidateArgs),
+    /// List likely alts and bots
+    Suspects(SuspectsArgs),
+    /// Show every display name of a user, or everyone who has used a display name
+    Names(NamesArgs),
+    /// Build the graph over weekly or monthly windows and follow how a circle changes
+    Timeline(TimelineArgs),
+}
+
+impl Command {
+    /// Whether the command builds the graph of the whole database, which doesn't need an owner.
+    #[must_use]
+    pub const fn is_whole_database(&self) -> bool {
+        match self {
+            Self::Analyze(args) => args.graph.scope.all,
+            Self::Export(args) => args.graph.scope.all,
+            Self::Suspects(_) => true,
+            Self::Names(_) => true,
+            Self::Timeline(args) => args.scope.all,
+            Self::InspectUser(_) | Self::Worlds(_) | Self::Validate(_) => false,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct OutputArgs {
+    /// Directory the outputs are written into
+    #[arg(long, short, default_value = ".")]
+    pub output_dir: PathBuf,
+}
+
+/// Which part of the database the graph covers and how its edges are weighted.
+#[derive(Debug, Args)]
+pub struct ScopeArgs {
+    /// Edge weight: `count`, `time` (hours), `days`, `log-count` or `log-time`
+    #[arg(long, short, default_value_t = WeightMode::Count)]
+    pub weight: WeightMode,
+
+    /// Number of hops from the owner whose neighbourhoods are collected, 1 is only the owner's
+    #[arg(long, default_value_t = 2)]
+    pub depth: usize,
+
+    /// Maximum number of neighbourhoods to collect, closest people first
+    #[arg(long)]
+    pub max_nodes: Option<usize>,
+
+    /// Build the graph of everyone in the database instead of starting from the owner
+    #[arg(long, conflicts_with_all = ["depth", "max_nodes"])]
+    pub all: bool,
+
+    /// With `--all`, leave out everyone seen in fewer sessions than this
+    #[arg(long, default_value_t = 1, requires = "all")]
+    pub min_sightings: u32,
+}
+
+#[derive(Debug, Args)]
+pub struct GraphArgs {
+    #[command(flatten)]
+    pub scope: ScopeArgs,
+
+    /// Resolution of the community detection, higher values give more and smaller circles
+    #[arg(long, default_value_t = DEFAULT_RESOLUTION)]
+    pub resolution: f64,
+
+    /// Only link two people in the undirected graph when each is at least this percentage of the
+    /// other's weight, instead of when either has seen the other
+    #[arg(long)]
+    pub mutual: Option<f64>,
+
+    /// Leave out the edges lighter than this from the k-cores and cliques of the circles
+    #[arg(long, default_value_t = CoreOptions::default().min_weight)]
+    pub min_weight: f64,
+
+    /// Smallest clique listed in the inner cores of the circles
+    #[arg(long, default_value_t = CoreOptions::default().min_clique)]
+    pub min_clique: usize,
+
+    /// Merge likely alts into a single node, the owner keeps their node
+    #[arg(long)]
+    pub merge_alts: bool,
+
+    /// Leave out likely bots, world hosts and cameras
+    #[arg(long)]
+    pub drop_bots: bool,
+
+    #[command(flatten)]
+    pub detection: DetectionArgs,
+
+    #[command(flatten)]
+    pub classifier: ClassifierArgs,
+}
+
+impl GraphArgs {
+    #[must_use]
+    pub const fn core_options(&self) -> CoreOptions {
+        CoreOptions {
+            min_weight: self.min_weight,
+            min_clique: self.min_clique,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct DetectionArgs {
+    /// Seconds the joins and the leaves of two accounts can be apart to count as alts
+    #[arg(long, default_value_t = DetectionOptions::default().alt_tolerance_secs)]
+    pub alt_tolerance: i64,
+
+    /// Visits two accounts need to share to count as alts
+    #[arg(long, default_value_t = DetectionOptions::default().alt_min_sessions)]
+    pub alt_min_sessions: u32,
+
+    /// Instances on a single day above which an account counts as a bot
+    #[arg(long, default_value_t = DetectionOptions::default().bot_instances_per_day)]
+    pub bot_instances_per_day: u32,
+}
+
+impl DetectionArgs {
+    #[must_use]
+    pub fn options(&self) -> DetectionOptions {
+        DetectionOptions {
+            alt_tolerance_secs: self.alt_tolerance,
+            alt_min_sessions: self.alt_min_sessions,
+            bot_instances_per_day: self.bot_instances_per_day,
+            ..DetectionOptions::default()
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct SuspectsArgs {
+    #[command(flatten)]
+    pub detection: DetectionArgs,
+
+    /// Number of alt pairs and bots to show
+    #[arg(long, default_value_t = 25)]
+    pub top: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct ClassifierArgs {
+    /// How friendships are inferred: `thresholds`, `z-score` or `logistic`
+    #[arg(long, default_value_t = Classifier::Thresholds)]
+    pub classifier: Classifier,
+
+    /// Calibrate the classifier against the owner's friend list in VRCX
+    #[arg(long)]
+    pub calibrate: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ValidateArgs {
+    /// Edge weight used for the percentages: `count`, `time` (hours), `days`, `log-count` or
+    /// `log-time`
+    #[arg(long, short, default_value_t = WeightMode::Count)]
+    pub weight: WeightMode,
+
+    #[command(flatten)]
+    pub classifier: ClassifierArgs,
+
+    /// Number of people to show in each list
+    #[arg(long, default_value_t = 10)]
+    pub top: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct AnalyzeArgs {
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    #[command(flatten)]
+    pub graph: GraphArgs,
+
+    /// Output formats to write
+    #[arg(long, short, value_enum, value_delimiter = ',', default_values_t = [Format::Ron, Format::Dot])]
+    pub format: Vec<Format>,
+
+    /// Number of people to show in the summary
+    #[arg(long, default_value_t = 10)]
+    pub top: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    #[command(flatten)]
+    pub graph: GraphArgs,
+
+    /// Output formats to write
+    #[arg(long, short, value_delimiter = ',', required = true)]
+    pub format: Vec<Format>,
+}
+
+#[derive(Debug, Args)]
+pub struct InspectUserArgs {
+    /// User id or display name to inspect
+    pub user: String,
+
+    /// Number of people to show
+    #[arg(long, default_value_t = 10)]
+    pub top: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct NamesArgs {
+    /// User id or display name
+    pub user: String,
+}
+
+#[derive(Debug, Args)]
+pub struct TimelineArgs {
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    /// Only the scope of the graph: the windows are built from the sessions as they are, so alts
+    /// and bots can't be merged or dropped
+    #[command(flatten)]
+    pub scope: ScopeArgs,
+
+    /// Length of every window: `week` or `month`
+    #[arg(long, default_value_t = Period::Month)]
+    pub period: Period,
+
+    /// Time between the starts of two windows, shorter than `--period` for sliding windows,
+    /// defaults to `--period`
+    #[arg(long)]
+    pub step: Option<Period>,
+
+    /// User id or display name whose circle is followed, defaults to the owner
+    #[arg(long)]
+    pub user: Option<String>,
+
+    /// Percentage of the user's weight in a window someone needs to be in their circle
+    #[arg(long, default_value_t = TimelineOptions::default().circle_share)]
+    pub circle_share: f64,
+}
+
+impl TimelineArgs {
+    /// # Errors
+    ///
+    /// Returns [`Error::Config`] if `--step` is longer than `--period`, which would leave gaps
+    /// between the windows.
+    pub fn options(&self) -> Result<TimelineOptions> {
+        let step = self.step.unwrap_or(self.period);
+        if step > self.period {
+            return Err(Error::Config(format!(
+                "--step {step} is longer than --period {}, the windows would leave gaps",
+                self.period
+            )));
+        }
+
+        Ok(TimelineOptions {
+            period: self.period,
+            step,
+            mode: self.scope.weight,
+            circle_share: self.circle_share,
+        })
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct WorldsArgs {
+    /// User id or display name to list the worlds of, defaults to the owner
+    #[arg(long)]
+    pub user: Option<String>,
+
+    /// Number of worlds to show
+    #[arg(long, default_value_t = 25)]
+    pub top: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Format {
+    /// `graph.ron`, `graph2_sorted.ron`, `sorted_undirected_graph.ron` and the other RON outputs
+    Ron,
+    /// The RON outputs as JSON, `graph.json`, `graph2_sorted.json` and so on
+    Json,
+    /// `nodes.csv` and `edges.csv`
+    Csv,
+    /// `graph.edgelist`, one `source target weight` line per edge
+    EdgeList,
+    /// `dot_edge_no_label.dot` and `dot_edge_with_label.dot`
+    Dot,
+    /// `graph.graphml`, for yEd and Gephi
+    Graphml,
+    /// `graph.gexf`, a dynamic graph for Gephi
+    Gexf,
+    /// `report.html`, an interactive page that works offline
+    Html,
+}
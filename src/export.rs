@@ -0,0 +1,337 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use petgraph::dot::{Config, Dot};
+use petgraph::graph::NodeIndex;
+
+use vrcx_insights::zaphkiel::error::Result;
+use vrcx_insights::zaphkiel::graph_xml::{
+    write_gexf, write_graphml, AttributedGraph, EdgeAttributes, NodeAttributes,
+};
+use vrcx_insights::zaphkiel::html_report::{write_html_report, HtmlReport};
+use vrcx_insights::zaphkiel::ids::Id;
+use vrcx_insights::zaphkiel::results::{JsonFormat, NameTable, Results, RonFormat};
+
+use crate::cli::Format;
+use crate::{label_of, metadata_graph, undirected_graph, MetadataGraph, Outputs};
+
+/// Number of people on each card of `report.html`.
+const REPORT_TOP: usize = 15;
+
+/// Writes the files of one output format into the output directory.
+pub trait Exporter {
+    /// # Errors
+    ///
+    /// Returns [`vrcx_insights::zaphkiel::error::Error::Io`] if a file can't be written.
+    fn export(&self, outputs: &Outputs, output_dir: &Path) -> Result<()>;
+}
+
+impl Format {
+    /// The exporter writing this format.
+    pub fn exporter(self) -> &'static dyn Exporter {
+        match self {
+            Self::Ron => &RonExporter,
+            Self::Json => &JsonExporter,
+            Self::Csv => &CsvExporter,
+            Self::EdgeList => &EdgeListExporter,
+            Self::Dot => &DotExporter,
+            Self::Graphml => &GraphmlExporter,
+            Self::Gexf => &GexfExporter,
+            Self::Html => &HtmlExporter,
+        }
+    }
+}
+
+/// Write every selected format into `output_dir`, creating it if needed.
+pub fn write_outputs(output_dir: &Path, formats: &[Format], outputs: &Outputs) -> Result<()> {
+    std::fs::create_dir_all(output_dir)?;
+    for format in formats {
+        format.exporter().export(outputs, output_dir)?;
+    }
+
+    Ok(())
+}
+
+/// `graph.ron`, `graph2_sorted.ron`, `communities.ron` and the others.
+pub struct RonExporter;
+
+impl Exporter for RonExporter {
+    fn export(&self, outputs: &Outputs, output_dir: &Path) -> Result<()> {
+        results_of(outputs).write::<RonFormat>(output_dir)
+    }
+}
+
+/// The same files as [`RonExporter`], as JSON.
+pub struct JsonExporter;
+
+impl Exporter for JsonExporter {
+    fn export(&self, outputs: &Outputs, output_dir: &Path) -> Result<()> {
+        results_of(outputs).write::<JsonFormat>(output_dir)
+    }
+}
+
+/// The serde outputs, in the schema [`vrcx_insights::load_results`] reads back.
+fn results_of(outputs: &Outputs) -> Results {
+    let graph = outputs
+        .graph
+        .iter()
+        .map(|(user_id, others)| {
+            let edges = others
+                .iter()
+                .map(|(other, co_presence)| (other.clone(), outputs.mode.weight(co_presence)))
+                .collect();
+            (user_id.clone(), outputs.node(user_id, edges))
+        })
+        .collect();
+    let graph2_sorted = outputs
+        .graph2_sorted
+        .iter()
+        .map(|(user_id, others)| (user_id.clone(), outputs.node(user_id, others.clone())))
+        .collect();
+
+    let mut sorted_undirected_graph = undirected_graph(&outputs.graph2_sorted, outputs.mutual)
+        .into_iter()
+        .map(|(user_id, others)| {
+            let mut others = others.into_iter().collect::<Vec<_>>();
+            others.sort();
+            let node = outputs.node(&user_id, others);
+            (user_id, node)
+        })
+        .collect::<Vec<_>>();
+    sorted_undirected_graph.sort_by(|(a_id, a), (b_id, b)| {
+        b.edges
+            .len()
+            .cmp(&a.edges.len())
+            .then_with(|| a_id.cmp(b_id))
+    });
+
+    Results {
+        graph,
+        graph2_sorted,
+        sorted_undirected_graph,
+        communities: Some(outputs.communities.clone()),
+        centrality: Some(outputs.centrality.clone()),
+        reciprocity: Some(outputs.reciprocity.clone()),
+        cores: Some(outputs.cores.clone()),
+        friends: Some(outputs.friends.clone()),
+        exclusions: Some(outputs.excluded.clone()),
+        suspects: Some(outputs.suspects.clone()),
+        names: Some(outputs.names.clone()),
+        timeline: None,
+    }
+}
+
+/// `nodes.csv` and `edges.csv`, with the columns of the GraphML attributes.
+pub struct CsvExporter;
+
+impl Exporter for CsvExporter {
+    fn export(&self, outputs: &Outputs, output_dir: &Path) -> Result<()> {
+        let graph = attributed_graph(outputs);
+
+        let mut nodes = BufWriter::new(File::create(output_dir.join("nodes.csv"))?);
+        writeln!(nodes, "id,name,community,degree,first_seen,last_seen")?;
+        for node in &graph.nodes {
+            let optional = |value: Option<String>| value.unwrap_or_default();
+            writeln!(
+                nodes,
+                "{},{},{},{},{},{}",
+                csv_escape(node.id.as_str()),
+                csv_escape(&node.name),
+                optional(node.community.map(|it| it.to_string())),
+                node.degree,
+                optional(node.first_seen.map(|it| it.to_rfc3339())),
+                optional(node.last_seen.map(|it| it.to_rfc3339())),
+            )?;
+        }
+        nodes.flush()?;
+
+        let mut edges = BufWriter::new(File::create(output_dir.join("edges.csv"))?);
+        writeln!(
+            edges,
+            "source,target,count,hours,weight,max,total,percentage,percentile"
+        )?;
+        for edge in &graph.edges {
+            let metadata = &edge.metadata;
+            writeln!(
+                edges,
+                "{},{},{},{},{},{},{},{},{}",
+                csv_escape(edge.source.as_str()),
+                csv_escape(edge.target.as_str()),
+                metadata.count,
+                crate::hours(edge.seconds),
+                metadata.weight,
+                metadata.max,
+                metadata.total,
+                metadata.percentage,
+                metadata.percentile,
+            )?;
+        }
+        edges.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Quote `value` if it contains anything CSV readers would split on.
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// `graph.edgelist`, one `source target weight` line per edge of `graph2_sorted.ron`, the format of
+/// `networkx.read_weighted_edgelist` and igraph's `ncol`.
+pub struct EdgeListExporter;
+
+impl Exporter for EdgeListExporter {
+    fn export(&self, outputs: &Outputs, output_dir: &Path) -> Result<()> {
+        let mut edges = BufWriter::new(File::create(output_dir.join("graph.edgelist"))?);
+        for (user_id, others) in &outputs.graph2_sorted {
+            for (other, metadata) in others {
+                writeln!(edges, "{user_id} {other} {}", metadata.weight)?;
+            }
+        }
+        edges.flush()?;
+
+        Ok(())
+    }
+}
+
+/// `dot_edge_no_label.dot` and `dot_edge_with_label.dot`.
+pub struct DotExporter;
+
+impl Exporter for DotExporter {
+    fn export(&self, outputs: &Outputs, output_dir: &Path) -> Result<()> {
+        write_dot(
+            output_dir,
+            &metadata_graph(&outputs.graph2_sorted),
+            &outputs.names,
+        )
+    }
+}
+
+/// `graph.graphml`.
+pub struct GraphmlExporter;
+
+impl Exporter for GraphmlExporter {
+    fn export(&self, outputs: &Outputs, output_dir: &Path) -> Result<()> {
+        let file = BufWriter::new(File::create(output_dir.join("graph.graphml"))?);
+        write_graphml(&attributed_graph(outputs), file)
+    }
+}
+
+/// `graph.gexf`.
+pub struct GexfExporter;
+
+impl Exporter for GexfExporter {
+    fn export(&self, outputs: &Outputs, output_dir: &Path) -> Result<()> {
+        let file = BufWriter::new(File::create(output_dir.join("graph.gexf"))?);
+        write_gexf(&attributed_graph(outputs), file)
+    }
+}
+
+/// `report.html`.
+pub struct HtmlExporter;
+
+impl Exporter for HtmlExporter {
+    fn export(&self, outputs: &Outputs, output_dir: &Path) -> Result<()> {
+        let report = HtmlReport::new(
+            "vrcx-insights".to_string(),
+            outputs.mode.to_string(),
+            &attributed_graph(outputs),
+            &outputs.communities,
+            REPORT_TOP,
+        );
+        let file = BufWriter::new(File::create(output_dir.join("report.html"))?);
+        write_html_report(&report, file)
+    }
+}
+
+/// `graph2_sorted` with every attribute GraphML and GEXF can carry.
+fn attributed_graph(outputs: &Outputs) -> AttributedGraph {
+    let membership = outputs.communities.membership();
+    let undirected = undirected_graph(&outputs.graph2_sorted, outputs.mutual);
+
+    let mut ids = undirected.keys().collect::<Vec<_>>();
+    ids.sort();
+    let nodes = ids
+        .into_iter()
+        .map(|user_id| {
+            let history = outputs.names.get(user_id).map(|(_, names)| names);
+            NodeAttributes {
+                id: user_id.clone(),
+                name: outputs.label(user_id).into(),
+                community: membership.get(user_id).copied(),
+                degree: undirected[user_id].len(),
+                first_seen: history.and_then(|names| names.iter().map(|it| it.first_seen).min()),
+                last_seen: history.and_then(|names| names.iter().map(|it| it.last_seen).max()),
+            }
+        })
+        .collect();
+
+    let edges = outputs
+        .graph2_sorted
+        .iter()
+        .flat_map(|(user_id, others)| {
+            others.iter().map(move |(other, metadata)| {
+                let co_presence = outputs
+                    .graph
+                    .get(user_id)
+                    .and_then(|it| it.get(other))
+                    .cloned()
+                    .unwrap_or_default();
+                EdgeAttributes {
+                    source: user_id.clone(),
+                    target: other.clone(),
+                    seconds: co_presence.seconds,
+                    metadata: *metadata,
+                    days: co_presence.days,
+                }
+            })
+        })
+        .collect();
+
+    AttributedGraph { nodes, edges }
+}
+
+/// Nodes are labelled with the display name, the user id is kept as the `id` attribute.
+fn write_dot(output_dir: &Path, petgraph: &MetadataGraph, names: &NameTable) -> Result<()> {
+    let node_attributes = |_, (_, user_id): (NodeIndex, &Id)| {
+        format!(
+            "label = \"{}\" id = \"{}\"",
+            dot_escape(label_of(names, user_id)),
+            dot_escape(user_id.as_str())
+        )
+    };
+    let no_attributes = |_, _| String::new();
+    let dot_edge_no_label = Dot::with_attr_getters(
+        petgraph,
+        &[Config::EdgeNoLabel, Config::NodeNoLabel],
+        &no_attributes,
+        &node_attributes,
+    );
+    let dot_edge_with_label = Dot::with_attr_getters(
+        petgraph,
+        &[Config::NodeNoLabel],
+        &no_attributes,
+        &node_attributes,
+    );
+
+    std::fs::write(
+        output_dir.join("dot_edge_no_label.dot"),
+        format!("{dot_edge_no_label:?}"),
+    )?;
+    std::fs::write(
+        output_dir.join("dot_edge_with_label.dot"),
+        format!("{dot_edge_with_label:?}"),
+    )?;
+
+    Ok(())
+}
+
+fn dot_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -1,64 +1,179 @@
-#![feature(lazy_cell)]
-
 use std::collections::{HashMap, HashSet};
 use std::hash::BuildHasher;
-use std::sync::{Arc, RwLock};
+use std::path::Path;
+use std::sync::{Arc, PoisonError, RwLock};
 
 use sqlx::SqlitePool;
 use tokio::task::JoinSet;
 
-use crate::zaphkiel::gamelog_join_leave::{GamelogJoinLeave, GamelogJoinLeaveRow};
-use crate::zaphkiel::is_kat::{Id, IsKat, Name, KAT_EXISTS};
+use crate::zaphkiel::co_presence::{others_in, CoPresence, CoPresenceGraph, CoPresenceMatrix};
+use crate::zaphkiel::error::{Error, Result};
+use crate::zaphkiel::friend_log::{FriendLogHistory, FriendLogHistoryRow};
+use crate::zaphkiel::gamelog_join_leave::GamelogJoinLeaveRow;
+use crate::zaphkiel::ids::{Id, Name};
+use crate::zaphkiel::name_history::{histories_of, NameHistory, NameHistoryRow};
+use crate::zaphkiel::query_options::QueryOptions;
+use crate::zaphkiel::results::Results;
+use crate::zaphkiel::session::Session;
 use crate::zaphkiel::world_instance::WorldInstance;
 
 pub mod zaphkiel {
+    pub mod centrality;
+    pub mod co_presence;
+    pub mod community;
+    pub mod config;
+    pub mod cores;
     pub mod cpu_info;
     pub mod db;
+    pub mod error;
+    pub mod exclusion;
+    pub mod friend_log;
+    pub mod friendship;
     pub mod gamelog_join_leave;
+    pub mod graph_xml;
     pub mod group_access_type;
-    pub mod is_kat;
+    pub mod html_report;
+    pub mod ids;
     pub mod join_leave_event;
     pub mod macros;
     pub mod metadata;
+    pub mod name_history;
+    pub mod query_options;
+    pub mod reciprocity;
+    pub mod results;
+    pub mod session;
+    pub mod suspects;
+    pub mod time_filter;
+    pub mod timeline;
+    pub mod validation;
+    pub mod weight;
     // pub mod vertex;
     pub mod world_instance;
     pub mod world_regions;
 }
 
-#[must_use]
+/// The outputs of an earlier run written into `dir` with the `ron` or `json` format, to work on
+/// them without querying the database again.
+///
+/// # Errors
+///
+/// See [`Results::load`].
 #[inline]
-pub async fn get_uuid_of(display_name: Name, pool: &SqlitePool) -> Id {
-    let q = "select *
-        from gamelog_join_leave
-        where display_name like ?
-        and user_id is not ''";
+pub fn load_results(dir: impl AsRef<Path>) -> Result<Results> {
+    Results::load(dir.as_ref())
+}
 
-    let row = sqlx::query_as::<_, GamelogJoinLeaveRow>(q)
-        .bind(display_name.to_string())
-        .fetch_one(pool)
-        .await
-        .unwrap();
-
-    assert!(
-        !row.user_id.is_empty(),
-        "No user_id found for {}",
-        display_name.to_string()
+/// Every display name `user_id` has used, with when.
+///
+/// # Errors
+///
+/// Returns [`Error::UnknownUser`] if `user_id` doesn't appear in the database, or
+/// [`Error::Parse`] if a row has an invalid timestamp.
+#[inline]
+pub async fn get_name_history(user_id: &Id, pool: &SqlitePool) -> Result<NameHistory> {
+    let q = format!("{NAME_HISTORY_QUERY} where user_id = ? group by user_id, display_name");
+
+    let rows = sqlx::query_as::<_, NameHistoryRow>(&q)
+        .bind(user_id.to_string())
+        .fetch_all(pool)
+        .await?;
+
+    histories_of(rows)?
+        .pop()
+        .ok_or_else(|| Error::UnknownUser(user_id.clone()))
+}
+
+/// The name history of everyone in `gamelog_join_leave`, in a single query.
+///
+/// # Errors
+///
+/// Returns an error if the query fails or a row has an invalid timestamp.
+#[inline]
+pub async fn get_name_histories(pool: &SqlitePool) -> Result<HashMap<Id, NameHistory>> {
+    let q = format!("{NAME_HISTORY_QUERY} where user_id is not '' group by user_id, display_name");
+
+    let rows = sqlx::query_as::<_, NameHistoryRow>(&q)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(histories_of(rows)?
+        .into_iter()
+        .map(|history| (history.user_id.clone(), history))
+        .collect())
+}
+
+const NAME_HISTORY_QUERY: &str = "select user_id,
+        display_name,
+        min(created_at) as first_seen,
+        max(created_at) as last_seen,
+        count(*) as sightings
+    from gamelog_join_leave";
+
+/// Everyone who has ever used `display_name` (ignoring case), with their whole name history, so
+/// someone who took the name of somebody else can be told apart.
+///
+/// # Errors
+///
+/// Returns an error if the query fails or a row has an invalid timestamp.
+#[inline]
+pub async fn get_users_named(display_name: &Name, pool: &SqlitePool) -> Result<Vec<NameHistory>> {
+    let q = format!(
+        "{NAME_HISTORY_QUERY}
+        where user_id in (
+            select user_id
+            from gamelog_join_leave
+            where display_name = ? collate nocase
+            and user_id is not ''
+        )
+        group by user_id, display_name"
     );
 
-    row.user_id.into()
+    let rows = sqlx::query_as::<_, NameHistoryRow>(&q)
+        .bind(display_name.to_string())
+        .fetch_all(pool)
+        .await?;
+
+    Ok(histories_of(rows)?)
+}
+
+/// The user id of the only user who has ever used `display_name`, see [`get_users_named`].
+///
+/// # Errors
+///
+/// Returns [`Error::UnknownName`] if nobody with a user id used `display_name`, or
+/// [`Error::AmbiguousName`] if more than one user did.
+#[inline]
+pub async fn get_uuid_of(display_name: Name, pool: &SqlitePool) -> Result<Id> {
+    let mut candidates = get_users_named(&display_name, pool).await?;
+
+    match candidates.len() {
+        0 => Err(Error::UnknownName(display_name)),
+        1 => Ok(candidates.remove(0).user_id),
+        _ => Err(Error::AmbiguousName(
+            display_name,
+            candidates.into_iter().map(|it| it.user_id).collect(),
+        )),
+    }
 }
 
+/// # Errors
+///
+/// Returns [`Error::UnknownUser`] if `user_id` doesn't appear in the database.
 #[inline]
 pub async fn get_display_name_for<S>(
     user_id: Id,
     pool: Arc<SqlitePool>,
     cache: Arc<RwLock<HashMap<Id, Arc<str>, S>>>,
-) -> Name
+) -> Result<Name>
 where
     S: BuildHasher + Send + Sync,
 {
-    if let Some(display_name) = cache.read().unwrap().get(&user_id) {
-        return display_name.clone().into();
+    if let Some(display_name) = cache
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&user_id)
+    {
+        return Ok(display_name.clone().into());
     }
 
     let q = "select *
@@ -67,104 +182,326 @@ where
         order by created_at desc
         limit 1";
 
-    let name: String = sqlx::query_as::<_, GamelogJoinLeaveRow>(q)
+    let row = sqlx::query_as::<_, GamelogJoinLeaveRow>(q)
         .bind(user_id.to_string())
-        .fetch_one(pool.as_ref())
-        .await
-        .unwrap()
-        .display_name;
+        .fetch_optional(pool.as_ref())
+        .await?;
+
+    let Some(row) = row else {
+        return Err(Error::UnknownUser(user_id));
+    };
 
-    let name: Arc<str> = name.into();
+    let name: Arc<str> = row.display_name.into();
 
-    cache.write().unwrap().insert(user_id, name.clone());
+    cache
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(user_id, name.clone());
 
-    name.into()
+    Ok(name.into())
 }
 
+/// The latest display name of every user in `gamelog_join_leave`, in a single query.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+#[inline]
+pub async fn get_display_names(pool: &SqlitePool) -> Result<HashMap<Id, Arc<str>>> {
+    let q = "select user_id, display_name
+        from gamelog_join_leave
+        where user_id is not ''
+        order by created_at";
+
+    let rows = sqlx::query_as::<_, (String, String)>(q)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(user_id, display_name)| (user_id.into(), display_name.into()))
+        .collect())
+}
+
+/// Name of a per-user VRCX table, e.g. `usr0123..._friend_log_current`.
 #[must_use]
 #[inline]
-pub async fn get_locations_for(user_id: Id, conn: Arc<SqlitePool>) -> HashSet<WorldInstance> {
+pub fn user_table(user_id: &Id, table: &str) -> String {
+    let prefix = user_id.to_string().replace(['-', '_'], "");
+    format!("{prefix}_{table}")
+}
+
+/// The current friends of `owner_id`, from VRCX's `<user>_friend_log_current` table.
+///
+/// VRCX only keeps this table for the accounts that logged into it, so it's only there for the
+/// owner of the database.
+///
+/// # Errors
+///
+/// Returns [`Error::MissingTable`] if there is no friend log for `owner_id`.
+#[inline]
+pub async fn get_friends_of(owner_id: &Id, pool: &SqlitePool) -> Result<HashSet<Id>> {
+    let table = user_table(owner_id, "friend_log_current");
+    ensure_table_exists(&table, pool).await?;
+
+    let q = format!("select user_id from {table} where user_id is not ''");
+    let friends = sqlx::query_scalar::<_, String>(&q).fetch_all(pool).await?;
+
+    Ok(friends.into_iter().map(Id::from).collect())
+}
+
+/// Every entry of VRCX's `<user>_friend_log_history` table of `owner_id`, see [`get_friends_of`].
+///
+/// # Errors
+///
+/// Returns [`Error::MissingTable`] if there is no friend log for `owner_id`, or
+/// [`Error::Parse`] if an entry has an invalid timestamp.
+#[inline]
+pub async fn get_friend_log_history_of(
+    owner_id: &Id,
+    pool: &SqlitePool,
+) -> Result<Vec<FriendLogHistory>> {
+    let table = user_table(owner_id, "friend_log_history");
+    ensure_table_exists(&table, pool).await?;
+
+    let q = format!(
+        "select id, created_at, type, user_id, display_name
+        from {table}
+        where user_id is not ''"
+    );
+    let rows = sqlx::query_as::<_, FriendLogHistoryRow>(&q)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(FriendLogHistory::try_from)
+        .collect::<Result<_, _>>()?)
+}
+
+async fn ensure_table_exists(table: &str, pool: &SqlitePool) -> Result<()> {
+    let exists = sqlx::query_scalar::<_, i64>(
+        "select count(*) from sqlite_master where type = 'table' and name = ?",
+    )
+    .bind(table)
+    .fetch_one(pool)
+    .await?;
+
+    if exists == 0 {
+        return Err(Error::MissingTable(table.to_owned()));
+    }
+    Ok(())
+}
+
+/// # Errors
+///
+/// Returns an error if the query fails or, depending on `options`, a row is malformed.
+#[inline]
+pub async fn get_locations_for(
+    user_id: Id,
+    conn: Arc<SqlitePool>,
+    options: &QueryOptions,
+) -> Result<HashSet<WorldInstance>> {
     let q = "select *
         from gamelog_join_leave
-        where user_id like ?";
+        where user_id like ?
+        and created_at >= ? and created_at < ?";
 
+    let (since, until) = options.time.created_at_range();
     let rows = sqlx::query_as::<_, GamelogJoinLeaveRow>(q)
         .bind(user_id.to_string())
+        .bind(since)
+        .bind(until)
         .fetch_all(conn.as_ref())
-        .await
-        .unwrap();
+        .await?;
 
-    rows.into_iter()
-        .map(std::convert::Into::into)
-        .filter_map(|row: GamelogJoinLeave| row.location)
-        .collect()
+    if !options.time.is_empty() {
+        // only the instances of the visits the filter keeps
+        return Ok(options
+            .sessions_of(rows)?
+            .into_iter()
+            .map(|session| session.instance)
+            .collect());
+    }
+    Ok(options
+        .parse_rows(rows)?
+        .into_iter()
+        .filter_map(|row| row.location)
+        .collect())
 }
 
-#[must_use]
+/// Every session of `user_id`, see [`QueryOptions::sessions_of`].
+///
+/// Only the rows of `user_id` are used, so a session without a leave event ends at the next event
+/// of the user in the same instance.
+///
+/// # Errors
+///
+/// Returns an error if the query fails or, depending on `options`, a row is malformed.
 #[inline]
-pub async fn get_others_for<S>(
+pub async fn get_sessions_for(
+    user_id: Id,
+    conn: Arc<SqlitePool>,
+    options: &QueryOptions,
+) -> Result<Vec<Session>> {
+    let q = "select *
+        from gamelog_join_leave
+        where user_id like ?
+        and created_at >= ? and created_at < ?";
+
+    let (since, until) = options.time.created_at_range();
+    let rows = sqlx::query_as::<_, GamelogJoinLeaveRow>(q)
+        .bind(user_id.to_string())
+        .bind(since)
+        .bind(until)
+        .fetch_all(conn.as_ref())
+        .await?;
+
+    options.sessions_of(rows)
+}
+
+/// Sessions of everyone in the database, from a single pass over `gamelog_join_leave`.
+///
+/// # Errors
+///
+/// Returns an error if the query fails or, depending on `options`, a row is malformed.
+#[inline]
+pub async fn get_all_sessions(
+    conn: Arc<SqlitePool>,
+    options: &QueryOptions,
+) -> Result<Vec<Session>> {
+    let q = "select *
+        from gamelog_join_leave
+        where location != ''
+        and user_id is not ''
+        and created_at >= ? and created_at < ?";
+
+    let (since, until) = options.time.created_at_range();
+    let rows = sqlx::query_as::<_, GamelogJoinLeaveRow>(q)
+        .bind(since)
+        .bind(until)
+        .fetch_all(conn.as_ref())
+        .await?;
+
+    options.sessions_of(rows)
+}
+
+/// Co-presence of every pair of users in the database, from a single pass over
+/// `gamelog_join_leave`.
+///
+/// Much faster than calling [`get_co_presence_for`] for a lot of users, at the cost of keeping the
+/// whole matrix in memory.
+///
+/// # Errors
+///
+/// Returns an error if the query fails or, depending on `options`, a row is malformed.
+#[inline]
+pub async fn get_co_presence_matrix(
+    conn: Arc<SqlitePool>,
+    options: &QueryOptions,
+) -> Result<CoPresenceMatrix> {
+    Ok(CoPresenceMatrix::from_sessions(
+        &get_all_sessions(conn, options).await?,
+    ))
+}
+
+/// The ego graph of `owner`, see [`CoPresenceMatrix::ego_graph`].
+///
+/// # Errors
+///
+/// Returns an error if the query fails or, depending on `options`, a row is malformed.
+#[inline]
+pub async fn build_ego_graph(
+    owner: &Id,
+    depth: usize,
+    max_nodes: Option<usize>,
+    conn: Arc<SqlitePool>,
+    options: &QueryOptions,
+) -> Result<CoPresenceGraph> {
+    Ok(get_co_presence_matrix(conn, options)
+        .await?
+        .ego_graph(owner, depth, max_nodes))
+}
+
+/// Everyone who was in one of `locations` at the same time as `user_id`, with how many times and
+/// for how long they overlapped, and how crowded the instances were.
+///
+/// # Errors
+///
+/// Returns an error if a query fails or, depending on `options`, a row is malformed.
+#[inline]
+pub async fn get_co_presence_for<S>(
     user_id: Id,
     conn: Arc<SqlitePool>,
     locations: HashSet<WorldInstance, S>,
-) -> HashMap<Id, u32>
+    options: &QueryOptions,
+) -> Result<HashMap<Id, CoPresence>>
 where
     S: BuildHasher + Send + Sync,
 {
-    let mut others = vec![];
-    // let len = locations.len();
+    let prefixes = locations
+        .into_iter()
+        .map(|location| location.get_prefix())
+        .collect::<HashSet<_>>();
+
     let mut handles = JoinSet::new();
-    for (idx, location) in locations.into_iter().enumerate() {
+    for prefix in prefixes {
         let conn = conn.clone();
         let user_id = user_id.clone();
+        let options = options.clone();
         handles.spawn(async move {
             let q = "select *
                     from gamelog_join_leave
                     where location like ?
                     and location != ''
-                    and user_id != ?
-                    and user_id is not ''";
+                    and user_id is not ''
+                    and created_at >= ? and created_at < ?";
 
-            let prefix = location.get_prefix();
             let location = format!("{prefix}%");
+            let (since, until) = options.time.created_at_range();
 
             let rows = sqlx::query_as::<_, GamelogJoinLeaveRow>(q)
                 .bind(location)
-                .bind(user_id.to_string())
+                .bind(since)
+                .bind(until)
                 .fetch_all(conn.as_ref())
-                .await
-                .unwrap();
-
-            let rows = rows.into_iter().map(std::convert::Into::into);
+                .await?;
 
-            let other = rows
-                .map(|row: GamelogJoinLeave| row.user_id.unwrap().into())
-                .filter(|it: &Id| !(*KAT_EXISTS && it.is_kat()))
-                .collect::<HashSet<_>>();
-
-            if other.is_empty() {
-                None
-            } else {
-                Some((idx, other))
-            }
+            let sessions = options.sessions_of(rows)?;
+            Ok::<_, Error>(others_in(&user_id, &prefix, &sessions))
         });
     }
 
-    handles.len();
+    let mut everyone_else: HashMap<Id, CoPresence> = HashMap::new();
 
     while let Some(handle) = handles.join_next().await {
-        if let Ok(Some((_idx, set))) = handle {
-            others.push(set);
+        for (other, co_presence) in handle?? {
+            *everyone_else.entry(other).or_default() += co_presence;
         }
     }
 
-    let mut everyone_else = HashMap::new();
-
-    for other in others {
-        for user_id in other {
-            let old = everyone_else.get(&user_id).unwrap_or(&0_u32);
-            everyone_else.insert(user_id, old + 1);
-        }
-    }
+    Ok(everyone_else)
+}
 
-    everyone_else
+/// Everyone who was in one of `locations` at the same time as `user_id`, with the number of times
+/// they overlapped.
+///
+/// # Errors
+///
+/// Returns an error if a query fails or, depending on `options`, a row is malformed.
+#[inline]
+pub async fn get_others_for<S>(
+    user_id: Id,
+    conn: Arc<SqlitePool>,
+    locations: HashSet<WorldInstance, S>,
+    options: &QueryOptions,
+) -> Result<HashMap<Id, u32>>
+where
+    S: BuildHasher + Send + Sync,
+{
+    Ok(get_co_presence_for(user_id, conn, locations, options)
+        .await?
+        .into_iter()
+        .map(|(other, co_presence)| (other, co_presence.overlaps))
+        .collect())
 }
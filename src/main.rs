@@ -1,207 +1,786 @@
+use std::borrow::Cow;
+use std::cmp::Reverse;
 use std::collections::{BTreeMap, HashMap, HashSet};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, PoisonError, RwLock};
 use std::time::Instant;
 
-use petgraph::dot::Config;
+use clap::Parser;
 use petgraph::Graph;
-use ron::ser::{to_writer_pretty, PrettyConfig};
-use tokio::task::JoinSet;
-use tokio::time::sleep;
+use sqlx::SqlitePool;
+use tokio::sync::OnceCell;
 
+use vrcx_insights::zaphkiel::centrality::{centralities, Centralities};
+use vrcx_insights::zaphkiel::co_presence::{CoPresenceGraph, CoPresenceMatrix};
+use vrcx_insights::zaphkiel::community::{louvain, Communities};
+use vrcx_insights::zaphkiel::cores::{cores, Cores};
 use vrcx_insights::zaphkiel::db::establish_connection;
-use vrcx_insights::zaphkiel::is_kat::{Id, IsKat, Name, KAT_DISPLAY_NAME, KAT_EXISTS, KAT_ID};
-use vrcx_insights::zaphkiel::metadata::Metadata;
-use vrcx_insights::{get_display_name_for, get_locations_for, get_others_for};
+use vrcx_insights::zaphkiel::error::{Error, Result};
+use vrcx_insights::zaphkiel::exclusion::ExclusionReport;
+use vrcx_insights::zaphkiel::friend_log::former_friends;
+use vrcx_insights::zaphkiel::friendship::{pair_stats, Confusion, FriendshipClassifier, PairStats};
+use vrcx_insights::zaphkiel::ids::{Id, Name};
+use vrcx_insights::zaphkiel::metadata::{metadata_of, Metadata};
+use vrcx_insights::zaphkiel::query_options::QueryOptions;
+use vrcx_insights::zaphkiel::reciprocity::Reciprocity;
+use vrcx_insights::zaphkiel::results::{write_document, FriendGraph, NameTable, Node, RonFormat};
+use vrcx_insights::zaphkiel::session::Session;
+use vrcx_insights::zaphkiel::suspects::Suspects;
+use vrcx_insights::zaphkiel::timeline::Timeline;
+use vrcx_insights::zaphkiel::validation::Validation;
+use vrcx_insights::zaphkiel::weight::WeightMode;
+use vrcx_insights::{
+    get_all_sessions, get_co_presence_for, get_display_name_for, get_display_names,
+    get_friend_log_history_of, get_friends_of, get_locations_for, get_name_histories,
+    get_name_history, get_sessions_for, get_users_named, get_uuid_of,
+};
+
+use crate::cli::{
+    AnalyzeArgs, ClassifierArgs, Cli, Command, ExportArgs, GraphArgs, InspectUserArgs, NamesArgs,
+    ScopeArgs, SuspectsArgs, TimelineArgs, ValidateArgs, WorldsArgs,
+};
+use crate::export::write_outputs;
+
+mod cli;
+mod export;
+
+type Cache = Arc<RwLock<HashMap<Id, Arc<str>>>>;
+type SortedGraph = BTreeMap<Id, Vec<(Id, Metadata)>>;
+type UndirectedGraph = HashMap<Id, HashSet<Id>>;
+type MetadataGraph = Graph<Id, Metadata>;
+type BoxedClassifier = Box<dyn FriendshipClassifier + Send + Sync>;
+
+/// What `build_graph` builds, the graph and what was found on the way.
+struct BuiltGraph {
+    graph: CoPresenceGraph,
+    suspects: Suspects,
+    names: NameTable,
+}
+
+/// Everything `write_outputs` writes, built from the graph of user ids.
+struct Outputs {
+    graph: CoPresenceGraph,
+    mode: WeightMode,
+    graph2_sorted: SortedGraph,
+    mutual: Option<f64>,
+    communities: Communities<Id>,
+    centrality: Centralities<Id>,
+    reciprocity: Reciprocity,
+    cores: Cores<Id>,
+    friends: FriendGraph,
+    excluded: ExclusionReport,
+    suspects: Suspects,
+    names: NameTable,
+}
+
+impl Outputs {
+    fn new(
+        built: BuiltGraph,
+        args: &GraphArgs,
+        classifier: &BoxedClassifier,
+        excluded: ExclusionReport,
+    ) -> Self {
+        let BuiltGraph {
+            graph,
+            suspects,
+            names,
+        } = built;
+        let graph2_sorted = sort_graph(&graph, args.scope.weight);
+        let petgraph = metadata_graph(&graph2_sorted);
+        let mut communities = louvain(&petgraph, |metadata| metadata.weight, args.resolution);
+        for community in &mut communities.communities {
+            community.name = format!("{}'s circle", label_of(&names, &community.members[0]));
+        }
+        let centrality = centralities(&petgraph, |metadata| metadata.weight, &communities);
+        let reciprocity = Reciprocity::of(&graph2_sorted);
+        let weights = graph2_sorted
+            .iter()
+            .flat_map(|(user_id, others)| {
+                others
+                    .iter()
+                    .map(move |(other, metadata)| ((user_id, other), metadata.weight))
+            })
+            .collect::<HashMap<_, _>>();
+        let cores = cores(
+            &undirected_graph(&graph2_sorted, args.mutual),
+            |a, b| {
+                let weight = |a, b| weights.get(&(a, b)).copied().unwrap_or(0_f64);
+                weight(a, b).max(weight(b, a))
+            },
+            &communities,
+            &args.core_options(),
+        );
+        let friends = pair_stats(&graph, args.scope.weight)
+            .into_iter()
+            .filter_map(|(user_id, others)| {
+                let mut friends = others
+                    .iter()
+                    .filter(|(_, stats)| classifier.is_friend(stats))
+                    .map(|(other, stats)| (other.clone(), classifier.score(stats)))
+                    .collect::<Vec<_>>();
+                friends.sort_by(|(a_id, a), (b_id, b)| b.total_cmp(a).then_with(|| a_id.cmp(b_id)));
+                (!friends.is_empty()).then_some((user_id, friends))
+            })
+            .collect();
+
+        Self {
+            graph,
+            mode: args.scope.weight,
+            graph2_sorted,
+            mutual: args.mutual,
+            communities,
+            centrality,
+            reciprocity,
+            cores,
+            friends,
+            excluded,
+            suspects,
+            names,
+        }
+    }
+
+    fn is_friend(&self, user_id: &Id, other: &Id) -> bool {
+        self.friends
+            .get(user_id)
+            .is_some_and(|friends| friends.iter().any(|(it, _)| it == other))
+    }
+
+    fn label<'a>(&'a self, user_id: &'a Id) -> &'a str {
+        label_of(&self.names, user_id)
+    }
+
+    fn node<E>(&self, user_id: &Id, edges: E) -> Node<E> {
+        Node {
+            name: self.label(user_id).into(),
+            edges,
+        }
+    }
+}
+
+/// The current display name of `user_id`, or the id itself if it has never been seen.
+fn label_of<'a>(names: &'a NameTable, user_id: &'a Id) -> &'a str {
+    names
+        .get(user_id)
+        .map_or_else(|| user_id.as_str(), |(current, _)| current.as_ref())
+}
+
+/// Everything a subcommand needs to talk to the database.
+#[derive(Clone)]
+struct Context {
+    owner_id: Option<Id>,
+    conn: Arc<SqlitePool>,
+    cache: Cache,
+    options: QueryOptions,
+    sessions: Arc<OnceCell<Vec<Session>>>,
+    matrix: Arc<OnceCell<CoPresenceMatrix>>,
+}
+
+impl Context {
+    fn owner_id(&self) -> Result<Id> {
+        self.owner_id.clone().ok_or_else(|| {
+            Error::Config("no owner id, pass --owner-id or --owner-id-file".to_owned())
+        })
+    }
+
+    /// Fill the display name cache with everyone in the database at once.
+    async fn preload_names(&self) -> Result<()> {
+        let names = get_display_names(&self.conn).await?;
+        self.cache
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(names);
+        Ok(())
+    }
+
+    async fn display_name(&self, user_id: Id) -> Result<Name> {
+        get_display_name_for(user_id, self.conn.clone(), self.cache.clone()).await
+    }
+
+    /// The sessions of everyone in the database, loaded on first use.
+    async fn sessions(&self) -> Result<&[Session]> {
+        self.sessions
+            .get_or_try_init(|| get_all_sessions(self.conn.clone(), &self.options))
+            .await
+            .map(Vec::as_slice)
+    }
+
+    /// The co-presence matrix of the whole database, built on first use.
+    async fn matrix(&self) -> Result<&CoPresenceMatrix> {
+        let sessions = self.sessions().await?;
+        self.matrix
+            .get_or_try_init(|| async { Ok(CoPresenceMatrix::from_sessions(sessions)) })
+            .await
+    }
+}
 
-#[allow(clippy::too_many_lines)]
 #[tokio::main(flavor = "multi_thread", worker_threads = 15)]
 async fn main() {
     let start = Instant::now();
 
-    let owner_id: Id = std::fs::read_to_string("owner_id.txt")
-        .unwrap()
-        .trim()
-        .into();
+    let cli = Cli::parse();
 
-    let conn = establish_connection().await;
-    let conn = Arc::new(conn);
+    if let Err(err) = run(cli).await {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
 
-    let cache = Arc::new(RwLock::new(HashMap::new()));
+    println!("\x07Total run time => {:?}", start.elapsed());
+}
 
-    let _owner_name = get_display_name_for(owner_id.clone(), conn.clone(), cache.clone()).await;
+async fn run(cli: Cli) -> Result<()> {
+    let owner_id = match cli.owner_id() {
+        Ok(owner_id) => Some(owner_id),
+        // the graph of the whole database doesn't need an owner
+        Err(_) if cli.command.is_whole_database() => None,
+        Err(err) => return Err(err),
+    };
+    let config = cli.load_config()?;
+    let conn = Arc::new(establish_connection(&cli.db_config(config.db)?).await?);
+
+    let ctx = Context {
+        owner_id,
+        conn,
+        cache: Arc::new(RwLock::new(HashMap::new())),
+        options: QueryOptions::new(cli.on_malformed())
+            .with_exclusions(config.exclude)
+            .with_time_filter(cli.time_filter(config.time)?),
+        sessions: Arc::default(),
+        matrix: Arc::default(),
+    };
 
-    KAT_DISPLAY_NAME
-        .set(get_display_name_for(KAT_ID.to_string().into(), conn.clone(), cache.clone()).await)
-        .unwrap();
+    match cli.command {
+        Command::Analyze(args) => analyze(&ctx, args).await?,
+        Command::Export(args) => export(&ctx, args).await?,
+        Command::InspectUser(args) => inspect_user(&ctx, args).await?,
+        Command::Worlds(args) => worlds(&ctx, args).await?,
+        Command::Validate(args) => validate(&ctx, args).await?,
+        Command::Suspects(args) => suspects(&ctx, args).await?,
+        Command::Names(args) => names(&ctx, args).await?,
+        Command::Timeline(args) => timeline(&ctx, args).await?,
+    }
+
+    if ctx.options.skipped() > 0 {
+        eprintln!("Skipped {} malformed rows", ctx.options.skipped());
+    }
+    let excluded = ctx.options.excluded();
+    if excluded.rows > 0 {
+        eprintln!(
+            "Excluded {} rows ({} people, {} worlds)",
+            excluded.rows,
+            excluded.users.len(),
+            excluded.worlds.len()
+        );
+    }
+
+    Ok(())
+}
 
-    let latest_name = get_display_name_for(owner_id.clone(), conn.clone(), cache.clone()).await;
+async fn analyze(ctx: &Context, args: AnalyzeArgs) -> Result<()> {
+    let outputs = build_outputs(ctx, &args.graph).await?;
+
+    if let Some(owner_id) = &ctx.owner_id {
+        let owner_name = ctx.display_name(owner_id.clone()).await?;
+        println!(
+            "Top {} people seen with {owner_name} by {}:",
+            args.top, args.graph.scope.weight
+        );
+        if let Some(others) = outputs.graph2_sorted.get(owner_id) {
+            for (other, metadata) in others.iter().take(args.top) {
+                let name = outputs.label(other);
+                let friend = if outputs.is_friend(owner_id, other) {
+                    ", friend"
+                } else {
+                    ""
+                };
+                println!(
+                    "    {name}: {} times, weight {:.2} ({}%, percentile-ish {}){friend}",
+                    metadata.count, metadata.weight, metadata.percentage, metadata.percentile
+                );
+            }
+        }
+    } else {
+        println!("{} people in the graph", outputs.graph.len());
+    }
 
-    let locations = get_locations_for(owner_id.clone(), conn.clone()).await;
+    let communities = &outputs.communities;
+    println!(
+        "Found {} circles (modularity {:.3}):",
+        communities.communities.len(),
+        communities.modularity
+    );
+    let circles = communities.communities.iter().zip(&outputs.cores.circles);
+    for (community, core) in circles.take(args.top) {
+        println!(
+            "    {}: {} people, cohesion {:.2}, density {:.2}, inner {}-core of {} people",
+            community.name,
+            community.members.len(),
+            community.cohesion,
+            community.density,
+            core.k,
+            core.core.len()
+        );
+    }
 
-    let others: Arc<HashMap<Id, u32>> = get_others_for(owner_id, conn.clone(), locations)
-        .await
-        .into();
+    let connectors = &outputs.centrality.connectors;
+    if !connectors.is_empty() {
+        println!("Top connectors between circles:");
+    }
+    for connector in connectors.iter().take(args.top) {
+        let circles = connector
+            .circles
+            .iter()
+            .map(|(name, share)| format!("{name} {:.0}%", share * 100_f64))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "    {}: betweenness {:.3}, participation {:.2} ({circles})",
+            outputs.label(&connector.node),
+            connector.betweenness,
+            connector.participation
+        );
+    }
 
-    let mut others_names: HashMap<Name, u32> = HashMap::new();
+    let pairs = &outputs.reciprocity.pairs;
+    if !pairs.is_empty() {
+        println!("Most one-sided pairs:");
+    }
+    for pair in pairs.iter().take(args.top) {
+        println!(
+            "    {} is {}% of {}'s time, but {} is {}% of theirs",
+            outputs.label(&pair.b),
+            pair.a_to_b,
+            outputs.label(&pair.a),
+            outputs.label(&pair.a),
+            pair.b_to_a
+        );
+    }
 
-    let mut handles = JoinSet::new();
+    write_outputs(&args.output.output_dir, &args.format, &outputs)
+}
 
-    for (user_id, count) in others.iter() {
-        let conn = conn.clone();
-        let cache = cache.clone();
-        let (user_id, count) = (user_id.clone(), count.to_owned());
-        handles.spawn(async move {
-            if user_id.is_kat() {
-                return None;
-            }
-            sleep(tokio::time::Duration::from_secs(10)).await;
-            let display_name =
-                get_display_name_for(user_id.clone(), conn.clone(), cache.clone()).await;
+async fn export(ctx: &Context, args: ExportArgs) -> Result<()> {
+    let outputs = build_outputs(ctx, &args.graph).await?;
+    write_outputs(&args.output.output_dir, &args.format, &outputs)
+}
 
-            if display_name.is_kat() {
-                return None;
-            }
+/// The graph, its classifier and everything computed from them, shared by `analyze` and `export`.
+async fn build_outputs(ctx: &Context, args: &GraphArgs) -> Result<Outputs> {
+    let built = build_graph(ctx, args).await?;
+    let classifier = classifier(ctx, &built.graph, args.scope.weight, &args.classifier).await?;
+
+    Ok(Outputs::new(
+        built,
+        args,
+        &classifier,
+        ctx.options.excluded(),
+    ))
+}
 
-            Some((display_name, count))
-        });
+/// The classifier picked by `args`, calibrated against the owner's friends if asked to.
+async fn classifier(
+    ctx: &Context,
+    graph: &CoPresenceGraph,
+    mode: WeightMode,
+    args: &ClassifierArgs,
+) -> Result<BoxedClassifier> {
+    if !args.calibrate {
+        return Ok(args.classifier.build());
     }
 
-    while let Some(res) = handles.join_next().await {
-        let res = res.unwrap();
-        match res {
-            None => {}
-            Some((name, count)) => {
-                others_names.insert(name, count);
-            }
+    let owner_id = ctx.owner_id()?;
+    let friends = get_friends_of(&owner_id, &ctx.conn).await?;
+
+    let samples = pair_stats(graph, mode)
+        .remove(&owner_id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(other, stats)| (stats, friends.contains(&other)))
+        .collect::<Vec<_>>();
+
+    Ok(calibrated(args, &samples))
+}
+
+fn calibrated(args: &ClassifierArgs, samples: &[(PairStats, bool)]) -> BoxedClassifier {
+    let mut classifier = args.classifier.build();
+    classifier.calibrate(samples);
+
+    let confusion = Confusion::of(samples, |stats| classifier.is_friend(stats));
+    println!(
+        "Calibrated {} against {} friends: precision {:.2}, recall {:.2}",
+        args.classifier,
+        samples.iter().filter(|(_, friend)| *friend).count(),
+        confusion.precision(),
+        confusion.recall()
+    );
+
+    classifier
+}
+
+async fn validate(ctx: &Context, args: ValidateArgs) -> Result<()> {
+    let owner_id = ctx.owner_id()?;
+    let owner_name = ctx.display_name(owner_id.clone()).await?;
+    let friends = get_friends_of(&owner_id, &ctx.conn).await?;
+    let history = get_friend_log_history_of(&owner_id, &ctx.conn).await?;
+    let former_friends = former_friends(&history)
+        .into_keys()
+        .filter(|it| !friends.contains(it))
+        .collect::<HashSet<_>>();
+
+    // with the neighbourhoods of everyone seen with the owner, like the default graph of
+    // `analyze`, so reciprocity has the edges back to the owner
+    let graph = ctx.matrix().await?.ego_graph(&owner_id, 2, None);
+    let stats = pair_stats(&graph, args.weight)
+        .remove(&owner_id)
+        .unwrap_or_default();
+
+    let classifier = if args.classifier.calibrate {
+        let samples = stats
+            .iter()
+            .map(|(other, stats)| (*stats, friends.contains(other)))
+            .collect::<Vec<_>>();
+        calibrated(&args.classifier, &samples)
+    } else {
+        args.classifier.classifier.build()
+    };
+    let validation = Validation::new(&stats, &friends, &former_friends, classifier.as_ref());
+
+    let confusion = validation.confusion;
+    println!(
+        "{} for {owner_name} against {} friends:",
+        args.classifier.classifier,
+        friends.len()
+    );
+    println!(
+        "    precision {:.2}, recall {:.2}, f1 {:.2}",
+        confusion.precision(),
+        confusion.recall(),
+        confusion.f1()
+    );
+    println!(
+        "    {} friends and {} others guessed right, {} strangers and {} friends guessed wrong",
+        confusion.true_positives,
+        confusion.true_negatives,
+        confusion.false_positives,
+        confusion.false_negatives
+    );
+    if args.classifier.calibrate {
+        println!("    (calibrated on the same friends, so this is optimistic)");
+    }
+
+    let lists = [
+        (
+            "Strangers you spend lots of time with",
+            &validation.strangers,
+        ),
+        ("Friends the classifier missed", &validation.missed_friends),
+        ("Former friends you still see", &validation.former_friends),
+    ];
+    for (title, list) in lists {
+        println!("{title} ({}):", list.len());
+        for (other, stats) in list.iter().take(args.top) {
+            println!(
+                "    {}: {:.1} hours on {} days, score {:.2}",
+                label(ctx, other).await?,
+                stats.hours,
+                stats.days,
+                classifier.score(stats)
+            );
         }
     }
 
-    let mut graph: HashMap<Name, HashMap<Name, u32>> = HashMap::new();
-    graph.insert(latest_name, others_names);
-
-    let mut handles = JoinSet::new();
-    for (user_id, _) in others.iter() {
-        let conn = conn.clone();
-        let cache = cache.clone();
-        let user_id = user_id.clone();
-        handles.spawn(async move {
-            let latest_name =
-                get_display_name_for(user_id.clone(), conn.clone(), cache.clone()).await;
-            let locations = get_locations_for(user_id.clone(), conn.clone()).await;
-            let others = get_others_for(user_id.clone(), conn.clone(), locations).await;
-
-            let mut others_name = HashMap::new();
-            for (user_id, count) in &others {
-                let name = get_display_name_for(user_id.clone(), conn.clone(), cache.clone()).await;
-                others_name.insert(name, count.to_owned());
-            }
+    println!(
+        "Friends you never see ({}):",
+        validation.unseen_friends.len()
+    );
+    for friend in validation.unseen_friends.iter().take(args.top) {
+        println!("    {}", label(ctx, friend).await?);
+    }
 
-            if latest_name.is_kat() && *KAT_EXISTS {
-                None
-            } else {
-                Some((latest_name, others_name))
-            }
-        });
+    Ok(())
+}
+
+/// `name (id)`, or just the id if `user_id` isn't in `gamelog_join_leave`.
+async fn label(ctx: &Context, user_id: &Id) -> Result<String> {
+    match ctx.display_name(user_id.clone()).await {
+        Ok(name) => Ok(format!("{name} ({user_id})")),
+        Err(Error::UnknownUser(id)) => Ok(id.to_string()),
+        Err(err) => Err(err),
     }
-    handles.join_next().await.into_iter().for_each(|handle| {
-        let _ = handle
-            .unwrap()
-            .and_then(|(node, edges)| graph.insert(node, edges));
-    });
+}
 
-    let graph = graph
-        .iter()
-        .map(|(name, edges)| {
-            (
-                name,
-                edges
-                    .iter()
-                    .map(|(name, count)| (name.to_owned(), count.to_owned()))
-                    .collect(),
-            )
-        })
-        .map(|(name, edges)| (name.to_owned(), edges))
-        .collect::<HashMap<Name, HashMap<Name, u32>>>();
+async fn suspects(ctx: &Context, args: SuspectsArgs) -> Result<()> {
+    let suspects = Suspects::detect(ctx.sessions().await?, &args.detection.options());
+    ctx.preload_names().await?;
+
+    println!("{} likely alt pairs:", suspects.alts.len());
+    for pair in suspects.alts.iter().take(args.top) {
+        println!(
+            "    {} and {}: {} visits together ({} and {} sessions)",
+            label(ctx, &pair.a).await?,
+            label(ctx, &pair.b).await?,
+            pair.shared,
+            pair.a_sessions,
+            pair.b_sessions
+        );
+    }
+
+    println!("{} likely bots:", suspects.bots.len());
+    for bot in suspects.bots.iter().take(args.top) {
+        println!(
+            "    {}: up to {} instances a day, {:.1} on average over {} days",
+            label(ctx, &bot.id).await?,
+            bot.peak_instances,
+            bot.mean_instances,
+            bot.days
+        );
+    }
+
+    Ok(())
+}
+
+/// A user id, or the id of the only user who has used a display name.
+async fn resolve_user(ctx: &Context, user: &str) -> Result<Id> {
+    let user = user.trim();
+    if user.starts_with("usr_") {
+        Ok(user.into())
+    } else {
+        get_uuid_of(Name(user.into()), &ctx.conn).await
+    }
+}
+
+async fn names(ctx: &Context, args: NamesArgs) -> Result<()> {
+    let user = args.user.trim();
+    let histories = if user.starts_with("usr_") {
+        vec![get_name_history(&user.into(), &ctx.conn).await?]
+    } else {
+        get_users_named(&Name(user.into()), &ctx.conn).await?
+    };
+    if histories.is_empty() {
+        return Err(Error::UnknownName(Name(user.into())));
+    }
+    if histories.len() > 1 {
+        println!("{} users have been named {user}:", histories.len());
+    }
+
+    for history in histories {
+        let current = history.current().cloned().unwrap_or_default();
+        println!("{current} ({})", history.user_id);
+        for name in history.names {
+            println!(
+                "    {}: {} to {}, {} events",
+                name.name,
+                name.first_seen.format("%Y-%m-%d"),
+                name.last_seen.format("%Y-%m-%d"),
+                name.sightings
+            );
+        }
+    }
 
-    let graph = graph
+    Ok(())
+}
+
+async fn timeline(ctx: &Context, args: TimelineArgs) -> Result<()> {
+    let options = args.options()?;
+    let user_id = match &args.user {
+        Some(user) => Some(resolve_user(ctx, user).await?),
+        None => ctx.owner_id.clone(),
+    };
+    let (graph, names) = select_graph(ctx, ctx.matrix().await?, &args.scope).await?;
+    let pairs = graph
         .iter()
-        .filter_map(|(node, edges)| {
-            if node.is_kat() && *KAT_EXISTS {
-                return None;
+        .flat_map(|(user_id, others)| others.keys().map(move |other| (user_id, other)));
+    let timeline = Timeline::build(ctx.sessions().await?, pairs, user_id.as_ref(), &options);
+
+    println!(
+        "{} windows of a {} every {}, {} pairs seen together",
+        timeline.windows.len(),
+        options.period,
+        options.step,
+        timeline.series.len()
+    );
+    if let Some(circle) = &timeline.circle {
+        println!("Circle of {}:", label_of(&names, &circle.user));
+        for (window, change) in timeline.windows.iter().zip(&circle.changes) {
+            let mut line = format!(
+                "    {}: {} people",
+                window.start.format("%Y-%m-%d"),
+                change.members.len()
+            );
+            for entered in &change.entered {
+                line.push_str(&format!(", +{}", label_of(&names, entered)));
             }
-            let edges = edges
-                .iter()
-                .filter(|(edge, _)| !(edge.is_kat() && *KAT_EXISTS))
-                .map(|(edge, count)| (edge.clone().0, count.to_owned()))
-                .collect::<HashMap<Arc<str>, u32>>();
-            if edges.is_empty() {
-                return None;
+            for left in &change.left {
+                line.push_str(&format!(", -{}", label_of(&names, left)));
             }
-            Some((node, edges))
-        })
-        .map(|(node, edges)| (node.clone().0, edges))
-        .collect::<HashMap<Arc<str>, HashMap<Arc<str>, u32>>>();
+            println!("{line}");
+        }
+    }
+
+    std::fs::create_dir_all(&args.output.output_dir)?;
+    write_document::<RonFormat, _>(&args.output.output_dir, "timeline", &timeline)
+}
+
+async fn inspect_user(ctx: &Context, args: InspectUserArgs) -> Result<()> {
+    let user_id = resolve_user(ctx, &args.user).await?;
+    let name = ctx.display_name(user_id.clone()).await?;
+    let locations = get_locations_for(user_id.clone(), ctx.conn.clone(), &ctx.options).await?;
+    let location_count = locations.len();
+    let sessions = get_sessions_for(user_id.clone(), ctx.conn.clone(), &ctx.options).await?;
+    let session_seconds = sessions
+        .iter()
+        .map(|session| u64::try_from(session.duration.num_seconds()).unwrap_or_default())
+        .sum();
+    let others =
+        get_co_presence_for(user_id.clone(), ctx.conn.clone(), locations, &ctx.options).await?;
+
+    let mut others = others.into_iter().collect::<Vec<_>>();
+    others.sort_by_key(|(_, co_presence)| Reverse(co_presence.seconds));
+
+    println!("{name} ({user_id})");
+    println!("    seen in {location_count} instances");
+    println!(
+        "    {} sessions, {:.1} hours in total",
+        sessions.len(),
+        hours(session_seconds)
+    );
+    println!("    seen with {} people, top {}:", others.len(), args.top);
+    for (other, co_presence) in others.into_iter().take(args.top) {
+        let other_name = ctx.display_name(other.clone()).await?;
+        println!(
+            "        {other_name} ({other}): {} times, {:.1} hours together",
+            co_presence.overlaps,
+            hours(co_presence.seconds)
+        );
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn hours(seconds: u64) -> f64 {
+    seconds as f64 / 3600_f64
+}
+
+async fn worlds(ctx: &Context, args: WorldsArgs) -> Result<()> {
+    let user_id = match args.user {
+        Some(user) => resolve_user(ctx, &user).await?,
+        None => ctx.owner_id()?,
+    };
+    let locations = get_locations_for(user_id.clone(), ctx.conn.clone(), &ctx.options).await?;
+
+    let mut worlds: HashMap<String, u32> = HashMap::new();
+    for location in locations {
+        *worlds.entry(location.world_id).or_default() += 1;
+    }
+
+    let mut worlds = worlds.into_iter().collect::<Vec<_>>();
+    worlds.sort_by(|(a_id, a), (b_id, b)| b.cmp(a).then_with(|| a_id.cmp(b_id)));
 
-    if std::fs::metadata("graph.ron").is_ok() {
-        std::fs::remove_file("graph.ron").unwrap();
+    println!(
+        "{user_id} has been seen in {} worlds, top {}:",
+        worlds.len(),
+        args.top
+    );
+    for (world_id, instances) in worlds.into_iter().take(args.top) {
+        println!("    {world_id}: {instances} instances");
     }
-    to_writer_pretty(
-        std::fs::File::create("graph.ron").unwrap(),
-        &graph,
-        PrettyConfig::default(),
-    )
-    .unwrap();
 
+    Ok(())
+}
+
+/// The graph selected by `args` and the suspects found on the way, with alts merged and bots
+/// dropped if asked to.
+async fn build_graph(ctx: &Context, args: &GraphArgs) -> Result<BuiltGraph> {
+    let suspects = Suspects::detect(ctx.sessions().await?, &args.detection.options());
+    let mut matrix = Cow::Borrowed(ctx.matrix().await?);
+    if args.merge_alts {
+        suspects.merge_alts(matrix.to_mut(), ctx.owner_id.as_ref());
+    }
+    if args.drop_bots {
+        suspects.drop_bots(matrix.to_mut(), ctx.owner_id.as_ref());
+    }
+    let (graph, names) = select_graph(ctx, &matrix, &args.scope).await?;
+
+    Ok(BuiltGraph {
+        graph,
+        suspects,
+        names,
+    })
+}
+
+/// The part of `matrix` selected by `args`, with the names of everyone in it.
+async fn select_graph(
+    ctx: &Context,
+    matrix: &CoPresenceMatrix,
+    args: &ScopeArgs,
+) -> Result<(CoPresenceGraph, NameTable)> {
+    let co_presence_graph = if args.all {
+        matrix.whole_graph(args.min_sightings)
+    } else {
+        matrix.ego_graph(&ctx.owner_id()?, args.depth, args.max_nodes)
+    };
+
+    let ids = co_presence_graph
+        .iter()
+        .flat_map(|(user_id, others)| std::iter::once(user_id).chain(others.keys()))
+        .collect::<HashSet<_>>();
+    let names = get_name_histories(&ctx.conn)
+        .await?
+        .into_iter()
+        .filter(|(user_id, _)| ids.contains(user_id))
+        .filter_map(|(user_id, history)| {
+            let current = history.current()?.clone();
+            Some((user_id, (current, history.names)))
+        })
+        .collect();
+
+    let graph = co_presence_graph
+        .into_iter()
+        .filter(|(_, others)| !others.is_empty())
+        .collect();
+
+    Ok((graph, names))
+}
+
+fn sort_graph(graph: &CoPresenceGraph, mode: WeightMode) -> SortedGraph {
     let graph2 = graph
         .iter()
-        .filter_map(|a| {
-            let (name, others) = a;
-            let total = others.values().sum::<u32>();
-            let max = *others.values().max().unwrap() + 1;
-            let new_others = others
-                .iter()
-                .map(|(k, count)| {
-                    let count = *count;
-                    let percentage =
-                        ((f64::from(count) * 10_000_f64) / f64::from(total)).round() / 100_f64;
-                    let percentile =
-                        ((f64::from(count) * 10_000_f64) / f64::from(max)).round() / 100_f64;
-                    (
-                        k.clone(),
-                        Metadata {
-                            count,
-                            max,
-                            total,
-                            percentage,
-                            percentile,
-                        },
-                    )
-                })
+        .filter_map(|(user_id, others)| {
+            let new_others = metadata_of(others, mode)
+                .into_iter()
+                .map(|(k, metadata)| (k.clone(), metadata))
                 .collect::<HashMap<_, Metadata>>();
             if new_others.is_empty() {
                 None
             } else {
-                Some((name.clone(), new_others))
+                Some((user_id.clone(), new_others))
             }
         })
         .collect::<HashMap<_, HashMap<_, _>>>();
 
-    let graph2_sorted = graph2
+    graph2
         .iter()
         .map(|(k, v)| {
             (k.clone(), {
                 let mut v = v.clone().into_iter().collect::<Vec<_>>();
-                v.sort_by_key(|(_, Metadata { count, .. })| *count);
-                v.reverse();
+                v.sort_by(|(a_id, a), (b_id, b)| {
+                    b.weight
+                        .total_cmp(&a.weight)
+                        .then_with(|| b.count.cmp(&a.count))
+                        .then_with(|| a_id.cmp(b_id))
+                });
                 v
             })
         })
-        .collect::<BTreeMap<_, _>>();
+        .collect()
+}
 
-    let graph2_sorted_set: HashMap<Arc<str>, HashMap<Arc<str>, _>> = graph2_sorted
+/// Everyone in `graph2_sorted` with the people they've been seen with, or with `mutual`, only the
+/// people who are at least that percentage of their weight and the other way around.
+fn undirected_graph(graph2_sorted: &SortedGraph, mutual: Option<f64>) -> UndirectedGraph {
+    let graph2_sorted_set: HashMap<Id, HashMap<Id, _>> = graph2_sorted
         .iter()
-        .map(|(name, v)| {
-            (name.clone(), {
+        .map(|(user_id, v)| {
+            (user_id.clone(), {
                 v.iter()
                     .map(|(a, b)| (a.to_owned(), b.to_owned()))
                     .collect()
@@ -209,111 +788,65 @@ async fn main() {
         })
         .collect();
 
-    if std::fs::metadata("graph2_sorted.ron").is_ok() {
-        std::fs::remove_file("graph2_sorted.ron").unwrap();
-    }
-    to_writer_pretty(
-        std::fs::File::create("graph2_sorted.ron").unwrap(),
-        &graph2_sorted,
-        PrettyConfig::default(),
-    )
-    .unwrap();
-
-    let undirected_graph = {
-        let mut adjacency_matrix: HashMap<_, HashSet<_>> = HashMap::new();
-        for (name, others) in graph2_sorted_set {
-            #[allow(clippy::option_if_let_else)] // adjacency_matrix is getting borrowed twice
-            let mut current_list: HashSet<_> = match adjacency_matrix.get(&name) {
+    let mut adjacency_matrix: HashMap<_, HashSet<_>> = HashMap::new();
+    for (name, others) in &graph2_sorted_set {
+        #[allow(clippy::option_if_let_else)] // adjacency_matrix is getting borrowed twice
+        let mut current_list: HashSet<_> = match adjacency_matrix.get(name) {
+            None => {
+                let ret = HashSet::new();
+                adjacency_matrix.insert(name.clone(), ret.clone());
+                ret
+            }
+            Some(set) => set.clone(),
+        };
+        let is_linked = |other: &Id| {
+            mutual.is_none_or(|threshold| {
+                others[other].percentage >= threshold
+                    && graph2_sorted_set
+                        .get(other)
+                        .and_then(|it| it.get(name))
+                        .is_some_and(|it| it.percentage >= threshold)
+            })
+        };
+        for other in others.keys().filter(|other| is_linked(other)) {
+            current_list.insert(other.clone());
+        }
+
+        for other in &current_list {
+            #[allow(clippy::option_if_let_else)] // we're borrowing adjacency_matrix twice
+            let mut other_list = match adjacency_matrix.get(other) {
                 None => {
                     let ret = HashSet::new();
-                    adjacency_matrix.insert(name.clone(), ret.clone());
+                    adjacency_matrix.insert(other.clone(), ret.clone());
                     ret
                 }
-                Some(set) => set.clone(),
+                Some(ret) => ret.clone(),
             };
-            for other in others.keys() {
-                current_list.insert(other.clone());
-            }
-
-            for other in &current_list {
-                #[allow(clippy::option_if_let_else)] // we're borrowing adjacency_matrix twice
-                let mut other_list = match adjacency_matrix.get(other) {
-                    None => {
-                        let ret = HashSet::new();
-                        adjacency_matrix.insert(other.clone(), ret.clone());
-                        ret
-                    }
-                    Some(ret) => ret.clone(),
-                };
-                other_list.insert(name.clone());
-                adjacency_matrix.insert(other.clone(), other_list);
-            }
-            adjacency_matrix.insert(name.clone(), current_list.clone());
+            other_list.insert(name.clone());
+            adjacency_matrix.insert(other.clone(), other_list);
         }
-        adjacency_matrix
-    };
-
-    let sorted_undirected_graph = {
-        let mut list = undirected_graph
-            .iter()
-            .map(|(k, v)| (k.clone(), v.clone()))
-            .collect::<Vec<_>>();
-        list.sort_by(|a, b| {
-            let (_, a) = a;
-            let (_, b) = b;
-            let a_len = a.len();
-            let b_len = b.len();
-            b_len.cmp(&a_len)
-        });
-        list
-    };
-
-    if std::fs::metadata("sorted_undirected_graph.ron").is_ok() {
-        std::fs::remove_file("sorted_undirected_graph.ron").unwrap();
+        adjacency_matrix.insert(name.clone(), current_list.clone());
     }
-    to_writer_pretty(
-        std::fs::File::create("sorted_undirected_graph.ron").unwrap(),
-        &sorted_undirected_graph,
-        PrettyConfig::default(),
-    )
-    .unwrap();
+    adjacency_matrix
+}
 
+fn metadata_graph(graph2_sorted: &SortedGraph) -> MetadataGraph {
     let mut petgraph = Graph::new();
     let mut dot_idxs = HashMap::new();
 
     for (node, edges) in graph2_sorted {
-        if node.is_kat() && !*KAT_EXISTS {
-            continue;
-        }
-
-        for (edge, weight) in &edges {
-            if edge.is_kat() && !*KAT_EXISTS {
-                continue;
-            }
-
-            dot_idxs
+        for (edge, weight) in edges {
+            let node_idx = *dot_idxs
                 .entry(node.clone())
                 .or_insert_with(|| petgraph.add_node(node.clone()));
-            let node_idx = dot_idxs.get(&node).unwrap().to_owned();
 
-            dot_idxs
+            let edge_idx = *dot_idxs
                 .entry(edge.clone())
                 .or_insert_with(|| petgraph.add_node(edge.clone()));
-            let edge_idx = dot_idxs.get(edge).unwrap().to_owned();
 
             petgraph.add_edge(node_idx, edge_idx, weight.to_owned());
         }
     }
 
-    let dot_edge_no_label = petgraph::dot::Dot::with_config(&petgraph, &[Config::EdgeNoLabel]);
-    let dot_edge_with_label = petgraph::dot::Dot::new(&petgraph);
-
-    std::fs::write("dot_edge_no_label.dot", format!("{dot_edge_no_label:?}")).unwrap();
-    std::fs::write(
-        "dot_edge_with_label.dot",
-        format!("{dot_edge_with_label:?}"),
-    )
-    .unwrap();
-
-    println!("\x07Total run time => {:?}", start.elapsed());
+    petgraph
 }
@@ -0,0 +1,458 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::fmt::Display;
+use std::hash::Hash;
+
+use petgraph::visit::EdgeRef;
+use petgraph::{EdgeType, Graph};
+use serde::{Deserialize, Serialize};
+
+use crate::zaphkiel::community::{adjacency_of, Adjacency, Communities};
+
+/// Probability of following an edge rather than jumping anywhere in [`centralities`]' PageRank.
+pub const DAMPING: f64 = 0.85;
+
+/// Iterations stop once the scores move by less than this.
+const TOLERANCE: f64 = 1e-10;
+const MAX_ITERATIONS: usize = 1000;
+/// Relative difference under which two paths count as equally short.
+const EQUAL_PATHS: f64 = 1e-12;
+
+/// How central someone is in the graph.
+///
+/// Distances along an edge are the inverse of its weight, so a heavy edge is a short one.
+///
+/// - `betweenness`: share of the shortest paths between two other people that go through them,
+///   from `0` to `1`.
+/// - `closeness`: inverse of their mean distance to everyone they can reach, scaled down by the
+///   share of the graph they can reach so small components don't come out on top.
+/// - `eigenvector`: how much weight they share with people who are central themselves, the most
+///   central person of every connected part of the graph has `1`.
+/// - `pagerank`: weighted PageRank over the directed edges, the scores of everyone sum up to `1`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Centrality<N> {
+    pub node: N,
+    pub betweenness: f64,
+    pub closeness: f64,
+    pub eigenvector: f64,
+    pub pagerank: f64,
+}
+
+/// Someone whose edges reach into more than one circle.
+///
+/// - `circles`: names of the circles they have weight in, with the share of their weight that
+///   goes there, highest first.
+/// - `participation`: how evenly their weight is spread over those circles, from `0` (all in one)
+///   towards `1` (spread over many).
+/// - `score`: `betweenness` times `participation`, what connectors are ranked by.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Connector<N> {
+    pub node: N,
+    pub circles: Vec<(String, f64)>,
+    pub betweenness: f64,
+    pub participation: f64,
+    pub score: f64,
+}
+
+/// Result of [`centralities`].
+///
+/// - `people`: everyone in the graph, highest PageRank first.
+/// - `connectors`: the people bridging circles, highest score first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Centralities<N> {
+    pub people: Vec<Centrality<N>>,
+    pub connectors: Vec<Connector<N>>,
+}
+
+/// Betweenness, closeness, eigenvector centrality and PageRank of every node, and the connectors
+/// between the circles of `communities`.
+///
+/// Like [`crate::zaphkiel::community::louvain`], edges are treated as undirected with the heavier
+/// direction winning, except for PageRank which follows every direction with its own weight.
+/// Edges without a positive weight are ignored.
+#[must_use]
+#[inline]
+pub fn centralities<N, E, Ty, F>(
+    graph: &Graph<N, E, Ty>,
+    weight: F,
+    communities: &Communities<N>,
+) -> Centralities<N>
+where
+    N: Clone + Display + Eq + Hash,
+    Ty: EdgeType,
+    F: Fn(&E) -> f64,
+{
+    let adjacency = adjacency_of(graph, &weight);
+    let betweenness = betweenness_of(&adjacency);
+    let closeness = closeness_of(&adjacency);
+    let eigenvector = eigenvector_of(&adjacency);
+    let pagerank = pagerank_of(graph, &weight);
+
+    let mut people = graph
+        .node_weights()
+        .enumerate()
+        .map(|(idx, node)| Centrality {
+            node: node.clone(),
+            betweenness: betweenness[idx],
+            closeness: closeness[idx],
+            eigenvector: eigenvector[idx],
+            pagerank: pagerank[idx],
+        })
+        .collect::<Vec<_>>();
+    people.sort_by(|a, b| {
+        b.pagerank
+            .total_cmp(&a.pagerank)
+            .then_with(|| a.node.to_string().cmp(&b.node.to_string()))
+    });
+
+    let membership = communities.membership();
+    let mut connectors = graph
+        .node_weights()
+        .enumerate()
+        .filter_map(|(idx, node)| {
+            let mut shares: BTreeMap<usize, f64> = BTreeMap::new();
+            for (other, weight) in &adjacency[idx] {
+                if let Some(circle) = membership.get(&graph[index(*other)]) {
+                    *shares.entry(*circle).or_default() += weight;
+                }
+            }
+            let total = shares.values().sum::<f64>();
+            if shares.len() < 2 || total <= 0_f64 {
+                return None;
+            }
+
+            let participation = 1_f64 - shares.values().map(|it| (it / total).powi(2)).sum::<f64>();
+            let mut circles = shares
+                .into_iter()
+                .map(|(circle, weight)| {
+                    (communities.communities[circle].name.clone(), weight / total)
+                })
+                .collect::<Vec<_>>();
+            circles.sort_by(|(a_name, a), (b_name, b)| {
+                b.total_cmp(a).then_with(|| a_name.cmp(b_name))
+            });
+            Some(Connector {
+                node: node.clone(),
+                circles,
+                betweenness: betweenness[idx],
+                participation,
+                score: betweenness[idx] * participation,
+            })
+        })
+        .filter(|connector| connector.score > 0_f64)
+        .collect::<Vec<_>>();
+    connectors.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.node.to_string().cmp(&b.node.to_string()))
+    });
+
+    Centralities { people, connectors }
+}
+
+/// A node waiting in Dijkstra's queue, the closest one comes out first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Visit {
+    distance: f64,
+    node: usize,
+}
+
+impl Eq for Visit {}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .distance
+            .total_cmp(&self.distance)
+            .then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Shortest paths from one node: the nodes in the order they were reached, the distance and
+/// number of shortest paths to every node, and the nodes right before each one on those paths.
+struct ShortestPaths {
+    order: Vec<usize>,
+    distances: Vec<f64>,
+    paths: Vec<f64>,
+    predecessors: Vec<Vec<usize>>,
+}
+
+fn shortest_paths(adjacency: &Adjacency, source: usize) -> ShortestPaths {
+    let count = adjacency.len();
+    let mut ret = ShortestPaths {
+        order: Vec::with_capacity(count),
+        distances: vec![f64::INFINITY; count],
+        paths: vec![0_f64; count],
+        predecessors: vec![vec![]; count],
+    };
+    let mut settled = vec![false; count];
+    ret.distances[source] = 0_f64;
+    ret.paths[source] = 1_f64;
+
+    let mut queue = BinaryHeap::from([Visit {
+        distance: 0_f64,
+        node: source,
+    }]);
+    while let Some(Visit { distance, node }) = queue.pop() {
+        if settled[node] {
+            continue;
+        }
+        settled[node] = true;
+        ret.order.push(node);
+        for (&other, &weight) in &adjacency[node] {
+            if settled[other] {
+                continue;
+            }
+            let candidate = distance + weight.recip();
+            let known = ret.distances[other];
+            // sums of inverses rarely come out exactly equal
+            if candidate < known * (1_f64 - EQUAL_PATHS) {
+                ret.distances[other] = candidate;
+                ret.paths[other] = ret.paths[node];
+                ret.predecessors[other] = vec![node];
+                queue.push(Visit {
+                    distance: candidate,
+                    node: other,
+                });
+            } else if candidate <= known * (1_f64 + EQUAL_PATHS) {
+                ret.paths[other] += ret.paths[node];
+                ret.predecessors[other].push(node);
+            }
+        }
+    }
+
+    ret
+}
+
+/// Brandes' algorithm, normalised by the number of pairs of other nodes.
+fn betweenness_of(adjacency: &Adjacency) -> Vec<f64> {
+    let count = adjacency.len();
+    let mut betweenness = vec![0_f64; count];
+    for source in 0..count {
+        let paths = shortest_paths(adjacency, source);
+        let mut dependency = vec![0_f64; count];
+        for &node in paths.order.iter().rev() {
+            for &before in &paths.predecessors[node] {
+                dependency[before] +=
+                    paths.paths[before] / paths.paths[node] * (1_f64 + dependency[node]);
+            }
+            if node != source {
+                betweenness[node] += dependency[node];
+            }
+        }
+    }
+
+    // every pair is counted from both ends, hence no halving
+    #[allow(clippy::cast_precision_loss)]
+    let pairs = count.saturating_sub(1) as f64 * count.saturating_sub(2) as f64;
+    if pairs > 0_f64 {
+        for it in &mut betweenness {
+            *it /= pairs;
+        }
+    }
+    betweenness
+}
+
+/// Wasserman and Faust's closeness, which stays meaningful when the graph isn't connected.
+fn closeness_of(adjacency: &Adjacency) -> Vec<f64> {
+    let count = adjacency.len();
+    (0..count)
+        .map(|source| {
+            let paths = shortest_paths(adjacency, source);
+            let total = paths
+                .order
+                .iter()
+                .map(|node| paths.distances[*node])
+                .sum::<f64>();
+            if total <= 0_f64 {
+                return 0_f64;
+            }
+            #[allow(clippy::cast_precision_loss)]
+            let reached = paths.order.len().saturating_sub(1) as f64;
+            #[allow(clippy::cast_precision_loss)]
+            let share = reached / count.saturating_sub(1) as f64;
+            reached / total * share
+        })
+        .collect()
+}
+
+/// Power iteration, on the adjacency plus the identity so it also settles on bipartite graphs.
+///
+/// Every connected component is scaled on its own, otherwise everything outside the component
+/// with the largest eigenvalue fades to zero.
+fn eigenvector_of(adjacency: &Adjacency) -> Vec<f64> {
+    let (components, count) = components_of(adjacency);
+    let mut scores = vec![1_f64; adjacency.len()];
+    for _ in 0..MAX_ITERATIONS {
+        let mut next = scores.clone();
+        for (node, row) in adjacency.iter().enumerate() {
+            for (&other, &weight) in row {
+                next[node] += weight * scores[other];
+            }
+        }
+        let mut max = vec![0_f64; count];
+        for (score, component) in next.iter().zip(&components) {
+            max[*component] = max[*component].max(*score);
+        }
+        for (score, component) in next.iter_mut().zip(&components) {
+            *score /= max[*component];
+        }
+        let change = next
+            .iter()
+            .zip(&scores)
+            .map(|(a, b)| (a - b).abs())
+            .sum::<f64>();
+        scores = next;
+        if change < TOLERANCE {
+            break;
+        }
+    }
+
+    // people without any edge only kept the identity's share
+    for (score, row) in scores.iter_mut().zip(adjacency) {
+        if row.is_empty() {
+            *score = 0_f64;
+        }
+    }
+    scores
+}
+
+/// The connected component of every node, numbered from `0`, and the number of components.
+fn components_of(adjacency: &Adjacency) -> (Vec<usize>, usize) {
+    let mut components = vec![usize::MAX; adjacency.len()];
+    let mut count = 0;
+    for start in 0..adjacency.len() {
+        if components[start] != usize::MAX {
+            continue;
+        }
+        components[start] = count;
+        let mut stack = vec![start];
+        while let Some(node) = stack.pop() {
+            for &other in adjacency[node].keys() {
+                if components[other] == usize::MAX {
+                    components[other] = count;
+                    stack.push(other);
+                }
+            }
+        }
+        count += 1;
+    }
+    (components, count)
+}
+
+/// PageRank following every edge with a probability proportional to its weight, nodes without
+/// outgoing edges jump anywhere.
+fn pagerank_of<N, E, Ty, F>(graph: &Graph<N, E, Ty>, weight: F) -> Vec<f64>
+where
+    Ty: EdgeType,
+    F: Fn(&E) -> f64,
+{
+    let count = graph.node_count();
+    if count == 0 {
+        return vec![];
+    }
+    let mut outgoing: Vec<HashMap<usize, f64>> = vec![HashMap::new(); count];
+    for edge in graph.edge_references() {
+        let (a, b) = (edge.source().index(), edge.target().index());
+        let weight = weight(edge.weight());
+        if a == b || weight.is_nan() || weight <= 0_f64 {
+            continue;
+        }
+        *outgoing[a].entry(b).or_default() += weight;
+        if !graph.is_directed() {
+            *outgoing[b].entry(a).or_default() += weight;
+        }
+    }
+    let totals = outgoing
+        .iter()
+        .map(|row| row.values().sum::<f64>())
+        .collect::<Vec<_>>();
+
+    #[allow(clippy::cast_precision_loss)]
+    let uniform = (count as f64).recip();
+    let mut ranks = vec![uniform; count];
+    for _ in 0..MAX_ITERATIONS {
+        let dangling = ranks
+            .iter()
+            .zip(&totals)
+            .filter(|(_, total)| **total <= 0_f64)
+            .map(|(rank, _)| rank)
+            .sum::<f64>();
+        let base = (1_f64 - DAMPING + DAMPING * dangling) * uniform;
+        let mut next = vec![base; count];
+        for (node, row) in outgoing.iter().enumerate() {
+            for (&other, &weight) in row {
+                next[other] += DAMPING * ranks[node] * weight / totals[node];
+            }
+        }
+        let change = next
+            .iter()
+            .zip(&ranks)
+            .map(|(a, b)| (a - b).abs())
+            .sum::<f64>();
+        ranks = next;
+        if change < TOLERANCE {
+            break;
+        }
+    }
+
+    ranks
+}
+
+fn index(node: usize) -> petgraph::graph::NodeIndex {
+    petgraph::graph::NodeIndex::new(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use petgraph::Graph;
+
+    use crate::zaphkiel::centrality::centralities;
+    use crate::zaphkiel::community::{louvain, DEFAULT_RESOLUTION};
+
+    #[test]
+    fn test_bridge_between_two_circles() {
+        // two triangles tied together by d
+        let mut graph = Graph::new();
+        let nodes = ["a", "b", "c", "d", "e", "f", "g"].map(|it| graph.add_node(it));
+        for (a, b) in [(0, 1), (1, 2), (0, 2), (4, 5), (5, 6), (4, 6)] {
+            graph.add_edge(nodes[a], nodes[b], 10_f64);
+        }
+        graph.add_edge(nodes[2], nodes[3], 2_f64);
+        graph.add_edge(nodes[3], nodes[4], 2_f64);
+
+        let communities = louvain(&graph, |it| *it, DEFAULT_RESOLUTION);
+        let centralities = centralities(&graph, |it| *it, &communities);
+        let of = |node| {
+            centralities
+                .people
+                .iter()
+                .find(|it| it.node == node)
+                .unwrap()
+        };
+
+        // every path from one triangle to the other goes through d
+        assert!((of("d").betweenness - 9_f64 / 15_f64).abs() < 1e-9);
+        assert!(of("d").betweenness > of("c").betweenness);
+        assert!(of("a").betweenness.abs() < 1e-9);
+        assert!(of("d").closeness > of("a").closeness);
+        assert!((of("a").eigenvector - of("g").eigenvector).abs() < 1e-6);
+        let total = centralities
+            .people
+            .iter()
+            .map(|it| it.pagerank)
+            .sum::<f64>();
+        assert!((total - 1_f64).abs() < 1e-6);
+
+        // d splits their weight evenly, c and e mostly stay with their own triangle
+        assert_eq!(centralities.connectors[0].node, "d");
+        assert!((centralities.connectors[0].participation - 0.5).abs() < 1e-9);
+        assert!(centralities.connectors.iter().all(|it| it.node != "a"));
+    }
+}
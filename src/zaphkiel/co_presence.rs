@@ -0,0 +1,489 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::ops::AddAssign;
+
+use chrono::NaiveDate;
+use sqlx::types::chrono::{DateTime, Utc};
+
+use crate::zaphkiel::ids::Id;
+use crate::zaphkiel::session::{intervals_by_user, Session};
+
+/// How much time two users spent in the same instance at the same time.
+///
+/// - `overlaps`: number of pairs of stays that overlapped.
+/// - `seconds`: total length of those overlaps.
+/// - `days`: the (UTC) days on which they overlapped.
+/// - `crowd_seconds`: `seconds` divided by the mean number of other people in each instance, so an
+///   hour in a packed public world counts for little and an hour alone together counts fully.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[allow(clippy::module_name_repetitions)]
+pub struct CoPresence {
+    pub overlaps: u32,
+    pub seconds: u64,
+    pub days: BTreeSet<NaiveDate>,
+    pub crowd_seconds: f64,
+}
+
+impl CoPresence {
+    #[must_use]
+    #[inline]
+    pub fn distinct_days(&self) -> u32 {
+        u32::try_from(self.days.len()).unwrap_or(u32::MAX)
+    }
+
+    /// The same co-presence in an instance with `population` people on average, see
+    /// [`mean_population`].
+    #[must_use]
+    #[inline]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn in_crowd(self, population: f64) -> Self {
+        Self {
+            crowd_seconds: self.seconds as f64 / (population - 1_f64).max(1_f64),
+            ..self
+        }
+    }
+}
+
+impl AddAssign for CoPresence {
+    #[inline]
+    fn add_assign(&mut self, rhs: Self) {
+        self.overlaps += rhs.overlaps;
+        self.seconds += rhs.seconds;
+        self.days.extend(rhs.days);
+        self.crowd_seconds += rhs.crowd_seconds;
+    }
+}
+
+/// A stay of a user in an instance, from joining to leaving, see [`crate::zaphkiel::session`].
+pub type Interval = (DateTime<Utc>, DateTime<Utc>);
+
+/// Overlap between two sorted lists of intervals, `crowd_seconds` is `seconds` until
+/// [`CoPresence::in_crowd`] says how crowded the instance was.
+#[must_use]
+#[inline]
+#[allow(clippy::cast_precision_loss)]
+pub fn overlap(a: &[Interval], b: &[Interval]) -> CoPresence {
+    let mut ret = CoPresence::default();
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() && j < b.len() {
+        let (a_start, a_end) = a[i];
+        let (b_start, b_end) = b[j];
+
+        let start = a_start.max(b_start);
+        let end = a_end.min(b_end);
+        if start < end {
+            ret.overlaps += 1;
+            ret.seconds += u64::try_from((end - start).num_seconds()).unwrap_or_default();
+            ret.days.extend(
+                start
+                    .date_naive()
+                    .iter_days()
+                    .take_while(|day| *day <= end.date_naive()),
+            );
+        }
+
+        if a_end < b_end {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    ret.crowd_seconds = ret.seconds as f64;
+
+    ret
+}
+
+/// Mean number of people in an instance while it was open, from the stays of everyone in it.
+#[must_use]
+#[inline]
+#[allow(clippy::cast_precision_loss)]
+pub fn mean_population<'a>(stays: impl IntoIterator<Item = &'a Interval>) -> f64 {
+    let mut span: Option<Interval> = None;
+    let mut seconds = 0;
+    for (start, end) in stays {
+        seconds += (*end - *start).num_seconds().max(0);
+        span = Some(span.map_or((*start, *end), |(first, last)| {
+            (first.min(*start), last.max(*end))
+        }));
+    }
+
+    match span {
+        Some((first, last)) if last > first => seconds as f64 / (last - first).num_seconds() as f64,
+        _ => 0_f64,
+    }
+}
+
+/// Everyone who was in the instance `prefix` (see
+/// [`crate::zaphkiel::world_instance::WorldInstance::get_prefix`]) at the same time as `user_id`.
+///
+/// Sessions of any other instance are left out, a `location like 'wrld_x:1%'` query also returns
+/// the sessions of `wrld_x:10` and `wrld_x:11`, which are different instances.
+#[must_use]
+#[inline]
+pub fn others_in(user_id: &Id, prefix: &str, sessions: &[Session]) -> Vec<(Id, CoPresence)> {
+    let sessions = sessions
+        .iter()
+        .filter(|session| session.instance.get_prefix() == prefix)
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut intervals = intervals_by_user(&sessions);
+    let population = mean_population(intervals.values().flatten());
+    let Some(own) = intervals.remove(user_id) else {
+        return vec![];
+    };
+
+    intervals
+        .into_iter()
+        .map(|(other, theirs)| (other, overlap(&own, &theirs).in_crowd(population)))
+        .filter(|(_, co_presence)| co_presence.overlaps > 0)
+        .collect()
+}
+
+/// Everyone each user has been seen with, `user -> other -> co-presence`.
+#[allow(clippy::module_name_repetitions)]
+pub type CoPresenceGraph = HashMap<Id, HashMap<Id, CoPresence>>;
+
+/// Co-presence of every pair of users, see [`CoPresenceMatrix::from_sessions`].
+#[derive(Debug, Clone, Default, PartialEq)]
+#[allow(clippy::module_name_repetitions)]
+pub struct CoPresenceMatrix {
+    pairs: HashMap<Id, HashMap<Id, CoPresence>>,
+    sightings: HashMap<Id, u32>,
+}
+
+impl CoPresenceMatrix {
+    /// Build the matrix in a single pass, sessions are grouped by instance and every pair of users
+    /// of an instance is overlapped once.
+    #[must_use]
+    #[inline]
+    pub fn from_sessions(sessions: &[Session]) -> Self {
+        let mut instances: HashMap<String, HashMap<&Id, Vec<Interval>>> = HashMap::new();
+        let mut sightings: HashMap<Id, u32> = HashMap::new();
+        for session in sessions {
+            *sightings.entry(session.user.clone()).or_default() += 1;
+            instances
+                .entry(session.instance.get_prefix())
+                .or_default()
+                .entry(&session.user)
+                .or_default()
+                .push(session.interval());
+        }
+
+        let mut pairs: HashMap<Id, HashMap<Id, CoPresence>> = HashMap::new();
+        for users in instances.into_values() {
+            let mut users = users.into_iter().collect::<Vec<_>>();
+            for (_, intervals) in &mut users {
+                intervals.sort();
+            }
+            let population = mean_population(users.iter().flat_map(|(_, it)| it.iter()));
+
+            for (i, (a, a_intervals)) in users.iter().enumerate() {
+                for (b, b_intervals) in &users[i + 1..] {
+                    let co_presence = overlap(a_intervals, b_intervals).in_crowd(population);
+                    if co_presence.overlaps == 0 {
+                        continue;
+                    }
+                    *pairs
+                        .entry((*a).clone())
+                        .or_default()
+                        .entry((*b).clone())
+                        .or_default() += co_presence.clone();
+                    *pairs
+                        .entry((*b).clone())
+                        .or_default()
+                        .entry((*a).clone())
+                        .or_default() += co_presence;
+                }
+            }
+        }
+
+        Self { pairs, sightings }
+    }
+
+    /// Everyone `user_id` has been seen with.
+    #[must_use]
+    #[inline]
+    pub fn get(&self, user_id: &Id) -> Option<&HashMap<Id, CoPresence>> {
+        self.pairs.get(user_id)
+    }
+
+    /// Everyone `user_id` has been seen with, empty if they've never been seen with anyone.
+    #[must_use]
+    #[inline]
+    pub fn others_of(&self, user_id: &Id) -> HashMap<Id, CoPresence> {
+        self.get(user_id).cloned().unwrap_or_default()
+    }
+
+    /// Number of sessions of `user_id`.
+    #[must_use]
+    #[inline]
+    pub fn sightings(&self, user_id: &Id) -> u32 {
+        self.sightings.get(user_id).copied().unwrap_or_default()
+    }
+
+    /// The neighbourhoods of everyone with at least `min_sightings` sessions, leaving out the
+    /// edges to everyone with fewer.
+    #[must_use]
+    #[inline]
+    pub fn whole_graph(&self, min_sightings: u32) -> CoPresenceGraph {
+        self.pairs
+            .iter()
+            .filter(|(user_id, _)| self.sightings(user_id) >= min_sightings)
+            .filter_map(|(user_id, others)| {
+                let others = others
+                    .iter()
+                    .filter(|(other, _)| self.sightings(other) >= min_sightings)
+                    .map(|(other, co_presence)| (other.clone(), co_presence.clone()))
+                    .collect::<HashMap<_, _>>();
+                (!others.is_empty()).then(|| (user_id.clone(), others))
+            })
+            .collect()
+    }
+
+    /// Everyone who has been seen with someone else.
+    #[inline]
+    pub fn users(&self) -> impl Iterator<Item = &Id> {
+        self.pairs.keys()
+    }
+
+    /// Breadth-first expansion from `owner`, with the neighbourhoods of everyone less than `depth`
+    /// hops away.
+    ///
+    /// A `depth` of 1 is only the neighbourhood of `owner`, 2 adds the neighbourhoods of everyone
+    /// seen with `owner` and `usize::MAX` is everyone `owner` is connected to. At most `max_nodes`
+    /// neighbourhoods are collected, closer people first and, at the same distance, the people
+    /// who spent the most time with whoever found them first.
+    #[must_use]
+    #[inline]
+    pub fn ego_graph(&self, owner: &Id, depth: usize, max_nodes: Option<usize>) -> CoPresenceGraph {
+        let mut graph = CoPresenceGraph::new();
+        let mut seen = HashSet::from([owner]);
+        let mut queue = VecDeque::from([(owner, 0)]);
+
+        while let Some((user_id, distance)) = queue.pop_front() {
+            if distance >= depth || max_nodes.is_some_and(|max| graph.len() >= max) {
+                break;
+            }
+            let Some(others) = self.get(user_id) else {
+                continue;
+            };
+
+            let mut next = others
+                .iter()
+                .filter(|(other, _)| !seen.contains(other))
+                .collect::<Vec<_>>();
+            next.sort_by_key(|(other, co_presence)| (Reverse(co_presence.seconds), *other));
+            for (other, _) in next {
+                seen.insert(other);
+                queue.push_back((other, distance + 1));
+            }
+
+            graph.insert(user_id.clone(), others.clone());
+        }
+
+        graph
+    }
+
+    /// Remove `user_id` from the matrix, as if they had never been seen.
+    #[inline]
+    pub fn remove(&mut self, user_id: &Id) {
+        self.sightings.remove(user_id);
+        if let Some(others) = self.pairs.remove(user_id) {
+            for other in others.keys() {
+                if let Some(theirs) = self.pairs.get_mut(other) {
+                    theirs.remove(user_id);
+                    if theirs.is_empty() {
+                        self.pairs.remove(other);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Merge `alt` into `keep`, as if they were the same person.
+    ///
+    /// Alts are seen together, so adding up their co-presence with a third person would count
+    /// every visit twice. The merged node keeps the larger of the two instead, and the days of
+    /// both.
+    #[inline]
+    pub fn merge(&mut self, keep: &Id, alt: &Id) {
+        if keep == alt {
+            return;
+        }
+        if let Some(sightings) = self.sightings.remove(alt) {
+            let entry = self.sightings.entry(keep.clone()).or_default();
+            *entry = (*entry).max(sightings);
+        }
+
+        let theirs = self.pairs.remove(alt).unwrap_or_default();
+        for (other, co_presence) in theirs {
+            if let Some(others) = self.pairs.get_mut(&other) {
+                others.remove(alt);
+            }
+            if other == *keep {
+                continue;
+            }
+            let merged = match self.pairs.get(keep).and_then(|it| it.get(&other)) {
+                Some(current) => CoPresence {
+                    overlaps: current.overlaps.max(co_presence.overlaps),
+                    seconds: current.seconds.max(co_presence.seconds),
+                    days: current.days.union(&co_presence.days).copied().collect(),
+                    crowd_seconds: current.crowd_seconds.max(co_presence.crowd_seconds),
+                },
+                None => co_presence,
+            };
+            self.pairs
+                .entry(other.clone())
+                .or_default()
+                .insert(keep.clone(), merged.clone());
+            self.pairs
+                .entry(keep.clone())
+                .or_default()
+                .insert(other, merged);
+        }
+        self.pairs.retain(|_, others| !others.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::types::chrono::{DateTime, TimeZone, Utc};
+
+    use crate::zaphkiel::co_presence::{others_in, overlap, CoPresence, CoPresenceMatrix};
+    use crate::zaphkiel::session::{Session, SessionEnd};
+
+    fn at(minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 1, 1, 0, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_overlap_counts_only_shared_time() {
+        let a = [(at(0), at(10)), (at(30), at(40))];
+        let b = [(at(5), at(15)), (at(20), at(25)), (at(35), at(50))];
+        let expected = CoPresence {
+            overlaps: 2,
+            seconds: 10 * 60,
+            days: [at(0).date_naive()].into(),
+            crowd_seconds: 600_f64,
+        };
+        assert_eq!(overlap(&a, &b), expected);
+    }
+
+    #[test]
+    fn test_matrix_is_symmetric_and_per_instance() {
+        let session = |user: &str, instance: &str, start, end| {
+            Session::new(
+                user.into(),
+                instance.try_into().unwrap(),
+                at(start),
+                at(end),
+                SessionEnd::Leave,
+            )
+        };
+        let matrix = CoPresenceMatrix::from_sessions(&[
+            session("a", "wrld_a:1", 0, 10),
+            session("b", "wrld_a:1", 5, 20),
+            session("c", "wrld_a:2", 0, 20),
+        ]);
+        let a_b = &matrix.get(&"a".into()).unwrap()[&"b".into()];
+        assert_eq!(a_b.seconds, 5 * 60);
+        assert_eq!(a_b, &matrix.get(&"b".into()).unwrap()[&"a".into()]);
+        assert!(matrix.get(&"c".into()).is_none());
+    }
+
+    #[test]
+    fn test_others_in_ignores_instances_sharing_the_prefix() {
+        let session = |user: &str, instance: &str, start, end| {
+            Session::new(
+                user.into(),
+                instance.try_into().unwrap(),
+                at(start),
+                at(end),
+                SessionEnd::Leave,
+            )
+        };
+        // what `location like 'wrld_a:1%'` returns, `:10` is another instance
+        let sessions = [
+            session("owner", "wrld_a:10~region(eu)", 0, 50),
+            session("x", "wrld_a:1~region(eu)", 0, 50),
+            session("owner", "wrld_a:1~region(eu)", 40, 45),
+            session("y", "wrld_a:10~region(eu)", 0, 50),
+        ];
+
+        let others = others_in(&"owner".into(), "wrld_a:1", &sessions);
+        assert_eq!(others.len(), 1);
+        let (other, co_presence) = &others[0];
+        assert_eq!(other, &"x".into());
+        assert_eq!(co_presence.seconds, 5 * 60);
+
+        let others = others_in(&"owner".into(), "wrld_a:10", &sessions);
+        assert_eq!(others.len(), 1);
+        assert_eq!(others[0].0, "y".into());
+        assert_eq!(others[0].1.seconds, 50 * 60);
+    }
+
+    #[test]
+    fn test_whole_graph_min_sightings() {
+        let session = |user: &str, start, end| {
+            Session::new(
+                user.into(),
+                "wrld_a:1".try_into().unwrap(),
+                at(start),
+                at(end),
+                SessionEnd::Leave,
+            )
+        };
+        let matrix = CoPresenceMatrix::from_sessions(&[
+            session("a", 0, 10),
+            session("b", 5, 20),
+            session("a", 30, 40),
+            session("b", 35, 50),
+            session("c", 45, 55),
+        ]);
+        assert_eq!(matrix.whole_graph(1).len(), 3);
+        let graph = matrix.whole_graph(2);
+        assert_eq!(graph.len(), 2);
+        assert!(!graph[&"b".into()].contains_key(&"c".into()));
+    }
+
+    #[test]
+    fn test_ego_graph_depth_and_cap() {
+        let session = |user: &str, start, end| {
+            Session::new(
+                user.into(),
+                "wrld_a:1".try_into().unwrap(),
+                at(start),
+                at(end),
+                SessionEnd::Leave,
+            )
+        };
+        // a chain a - b - c - d
+        let matrix = CoPresenceMatrix::from_sessions(&[
+            session("a", 0, 10),
+            session("b", 5, 20),
+            session("c", 15, 30),
+            session("d", 25, 40),
+        ]);
+        let nodes = |depth, max_nodes| {
+            let mut nodes = matrix
+                .ego_graph(&"a".into(), depth, max_nodes)
+                .into_keys()
+                .map(|it| it.to_string())
+                .collect::<Vec<_>>();
+            nodes.sort();
+            nodes
+        };
+        assert_eq!(nodes(1, None), ["a"]);
+        assert_eq!(nodes(2, None), ["a", "b"]);
+        assert_eq!(nodes(usize::MAX, None), ["a", "b", "c", "d"]);
+        assert_eq!(nodes(usize::MAX, Some(3)), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_no_overlap_for_visits_at_different_times() {
+        let a = [(at(0), at(10))];
+        let b = [(at(10), at(20))];
+        assert_eq!(overlap(&a, &b), CoPresence::default());
+    }
+}
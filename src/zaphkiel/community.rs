@@ -0,0 +1,333 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+
+use petgraph::visit::EdgeRef;
+use petgraph::{EdgeType, Graph};
+use serde::{Deserialize, Serialize};
+
+/// Default resolution of [`louvain`], higher values give more and smaller communities.
+pub const DEFAULT_RESOLUTION: f64 = 1.0;
+
+/// Moves that improve the modularity by less than this are ignored, so rounding errors can't make
+/// the optimisation loop forever.
+const MIN_GAIN: f64 = 1e-12;
+
+/// A friend circle found by [`louvain`].
+///
+/// - `name`: named after the member with the most weight inside the community.
+/// - `members`: sorted by their weight inside the community, highest first.
+/// - `internal_weight`: sum of the weights of the edges between members.
+/// - `cohesion`: share of the members' total edge weight that stays inside the community, from
+///   `0` (everyone mostly hangs out with outsiders) to `1` (a closed group).
+/// - `density`: share of the pairs of members that have an edge at all.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Community<N> {
+    pub name: String,
+    pub members: Vec<N>,
+    pub internal_weight: f64,
+    pub cohesion: f64,
+    pub density: f64,
+}
+
+/// Result of [`louvain`], communities are sorted by size, largest first.
+///
+/// `modularity` is the standard (resolution 1) modularity of the partition, so runs with
+/// different resolutions can be compared.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Communities<N> {
+    pub resolution: f64,
+    pub modularity: f64,
+    pub communities: Vec<Community<N>>,
+}
+
+impl<N> Communities<N> {
+    /// Index into `communities` for every member.
+    #[must_use]
+    #[inline]
+    pub fn membership(&self) -> HashMap<&N, usize>
+    where
+        N: Eq + std::hash::Hash,
+    {
+        self.communities
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, community)| community.members.iter().map(move |it| (it, idx)))
+            .collect()
+    }
+}
+
+/// Symmetric weighted adjacency lists, self loops hold twice the weight inside a node so every row
+/// sums up to the degree of its node.
+pub(crate) type Adjacency = Vec<BTreeMap<usize, f64>>;
+
+/// Louvain community detection.
+///
+/// Edges are treated as undirected, when both `a -> b` and `b -> a` exist the heavier one is used
+/// (co-presence is symmetric, the two directions only differ in what's been queried). Edges without
+/// a positive weight are ignored. Nodes without any edge end up in a community of their own.
+///
+/// The result is deterministic for a given graph.
+#[must_use]
+#[inline]
+pub fn louvain<N, E, Ty, F>(graph: &Graph<N, E, Ty>, weight: F, resolution: f64) -> Communities<N>
+where
+    N: Clone + Display,
+    Ty: EdgeType,
+    F: Fn(&E) -> f64,
+{
+    let adjacency = adjacency_of(graph, weight);
+
+    // community of every original node
+    let mut membership = (0..adjacency.len()).collect::<Vec<_>>();
+    let mut level = adjacency.clone();
+    loop {
+        let (moved, partition) = one_level(&level, resolution);
+        if !moved {
+            break;
+        }
+        let (partition, count) = renumber(&partition);
+        for community in &mut membership {
+            *community = partition[*community];
+        }
+        level = aggregate(&level, &partition, count);
+    }
+
+    let (membership, count) = renumber(&membership);
+    summarize(graph, &adjacency, &membership, count, resolution)
+}
+
+pub(crate) fn adjacency_of<N, E, Ty, F>(graph: &Graph<N, E, Ty>, weight: F) -> Adjacency
+where
+    Ty: EdgeType,
+    F: Fn(&E) -> f64,
+{
+    let mut adjacency: Adjacency = vec![BTreeMap::new(); graph.node_count()];
+    for edge in graph.edge_references() {
+        let (a, b) = (edge.source().index(), edge.target().index());
+        let weight = weight(edge.weight());
+        if a == b || weight.is_nan() || weight <= 0_f64 {
+            continue;
+        }
+        for (from, to) in [(a, b), (b, a)] {
+            let entry = adjacency[from].entry(to).or_insert(0_f64);
+            *entry = entry.max(weight);
+        }
+    }
+    adjacency
+}
+
+/// Move single nodes between communities until nothing improves anymore.
+fn one_level(adjacency: &Adjacency, resolution: f64) -> (bool, Vec<usize>) {
+    let degrees = adjacency
+        .iter()
+        .map(|row| row.values().sum::<f64>())
+        .collect::<Vec<_>>();
+    let m2 = degrees.iter().sum::<f64>();
+    let mut community = (0..adjacency.len()).collect::<Vec<_>>();
+    if m2 <= 0_f64 {
+        return (false, community);
+    }
+    let mut totals = degrees.clone();
+
+    let mut moved = false;
+    loop {
+        let mut improved = false;
+        for node in 0..adjacency.len() {
+            let current = community[node];
+            let degree = degrees[node];
+
+            let mut links: BTreeMap<usize, f64> = BTreeMap::new();
+            links.insert(current, 0_f64);
+            for (&other, &weight) in &adjacency[node] {
+                if other != node {
+                    *links.entry(community[other]).or_default() += weight;
+                }
+            }
+
+            totals[current] -= degree;
+            let gain = |(candidate, weight): (&usize, &f64)| {
+                weight - resolution * totals[*candidate] * degree / m2
+            };
+            let mut best = (current, gain((&current, &links[&current])));
+            for link in &links {
+                let gain = gain(link);
+                if gain > best.1 + MIN_GAIN {
+                    best = (*link.0, gain);
+                }
+            }
+            totals[best.0] += degree;
+
+            if best.0 != current {
+                community[node] = best.0;
+                improved = true;
+                moved = true;
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+
+    (moved, community)
+}
+
+/// Renumber communities to `0..count`, in order of first appearance.
+fn renumber(partition: &[usize]) -> (Vec<usize>, usize) {
+    let mut ids = HashMap::new();
+    let partition = partition
+        .iter()
+        .map(|community| {
+            let next = ids.len();
+            *ids.entry(*community).or_insert(next)
+        })
+        .collect();
+    (partition, ids.len())
+}
+
+/// Turn every community into a single node.
+fn aggregate(adjacency: &Adjacency, partition: &[usize], count: usize) -> Adjacency {
+    let mut ret: Adjacency = vec![BTreeMap::new(); count];
+    for (node, row) in adjacency.iter().enumerate() {
+        for (&other, &weight) in row {
+            *ret[partition[node]].entry(partition[other]).or_default() += weight;
+        }
+    }
+    ret
+}
+
+fn summarize<N, E, Ty>(
+    graph: &Graph<N, E, Ty>,
+    adjacency: &Adjacency,
+    membership: &[usize],
+    count: usize,
+    resolution: f64,
+) -> Communities<N>
+where
+    N: Clone + Display,
+    Ty: EdgeType,
+{
+    let m2 = adjacency
+        .iter()
+        .map(|row| row.values().sum::<f64>())
+        .sum::<f64>();
+
+    let mut members: Vec<Vec<(usize, f64)>> = vec![vec![]; count];
+    // (sum of weights inside counted twice, sum of degrees, number of edges inside)
+    let mut sums = vec![(0_f64, 0_f64, 0_usize); count];
+    for (node, row) in adjacency.iter().enumerate() {
+        let community = membership[node];
+        let inside = row
+            .iter()
+            .filter(|(other, _)| membership[**other] == community)
+            .map(|(_, weight)| *weight)
+            .sum::<f64>();
+        members[community].push((node, inside));
+        sums[community].0 += inside;
+        sums[community].1 += row.values().sum::<f64>();
+        sums[community].2 += row
+            .keys()
+            .filter(|other| membership[**other] == community)
+            .count();
+    }
+
+    let modularity = if m2 > 0_f64 {
+        sums.iter()
+            .map(|(inside, total, _)| inside / m2 - (total / m2).powi(2))
+            .sum()
+    } else {
+        0_f64
+    };
+
+    let mut communities = members
+        .into_iter()
+        .zip(sums)
+        .map(|(mut members, (inside, total, edges))| {
+            members.sort_by(|(a, a_weight), (b, b_weight)| {
+                b_weight.total_cmp(a_weight).then_with(|| {
+                    graph[index(*a)]
+                        .to_string()
+                        .cmp(&graph[index(*b)].to_string())
+                })
+            });
+            let size = members.len();
+            #[allow(clippy::cast_precision_loss)]
+            let density = if size > 1 {
+                edges as f64 / (size * (size - 1)) as f64
+            } else {
+                0_f64
+            };
+            Community {
+                name: format!("{}'s circle", graph[index(members[0].0)]),
+                members: members
+                    .into_iter()
+                    .map(|(node, _)| graph[index(node)].clone())
+                    .collect(),
+                internal_weight: inside / 2_f64,
+                cohesion: if total > 0_f64 { inside / total } else { 0_f64 },
+                density,
+            }
+        })
+        .collect::<Vec<_>>();
+    communities.sort_by(|a, b| {
+        b.members
+            .len()
+            .cmp(&a.members.len())
+            .then_with(|| b.internal_weight.total_cmp(&a.internal_weight))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+
+    Communities {
+        resolution,
+        modularity,
+        communities,
+    }
+}
+
+fn index(node: usize) -> petgraph::graph::NodeIndex {
+    petgraph::graph::NodeIndex::new(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use petgraph::Graph;
+
+    use crate::zaphkiel::community::{louvain, DEFAULT_RESOLUTION};
+
+    #[test]
+    fn test_two_cliques_with_a_bridge() {
+        let mut graph = Graph::new();
+        let nodes = ["a", "b", "c", "d", "e", "f", "g", "h"].map(|it| graph.add_node(it));
+        for clique in [&nodes[..4], &nodes[4..]] {
+            for (i, a) in clique.iter().enumerate() {
+                for b in &clique[i + 1..] {
+                    graph.add_edge(*a, *b, 10_f64);
+                }
+            }
+        }
+        graph.add_edge(nodes[3], nodes[4], 1_f64);
+
+        let communities = louvain(&graph, |it| *it, DEFAULT_RESOLUTION);
+        assert_eq!(communities.communities.len(), 2);
+        let membership = communities.membership();
+        assert_eq!(membership[&"a"], membership[&"d"]);
+        assert_eq!(membership[&"e"], membership[&"h"]);
+        assert_ne!(membership[&"a"], membership[&"h"]);
+        assert!(communities.modularity > 0.4);
+        for community in &communities.communities {
+            assert!((community.density - 1_f64).abs() < 1e-9);
+            assert!(community.cohesion > 0.9);
+        }
+    }
+
+    #[test]
+    fn test_higher_resolution_splits_more() {
+        let mut graph = Graph::new();
+        let nodes = ["a", "b", "c", "d"].map(|it| graph.add_node(it));
+        graph.add_edge(nodes[0], nodes[1], 5_f64);
+        graph.add_edge(nodes[1], nodes[2], 1_f64);
+        graph.add_edge(nodes[2], nodes[3], 5_f64);
+
+        assert_eq!(louvain(&graph, |it| *it, 0.01).communities.len(), 1);
+        assert_eq!(louvain(&graph, |it| *it, 10_f64).communities.len(), 4);
+    }
+}
@@ -0,0 +1,107 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::zaphkiel::db::DbConfig;
+use crate::zaphkiel::error::{Error, Result};
+use crate::zaphkiel::exclusion::ExclusionList;
+use crate::zaphkiel::time_filter::TimeFilter;
+
+pub const DEFAULT_CONFIG_PATH: &str = "vrcx-insights.ron";
+
+/// The optional `vrcx-insights.ron` config file.
+///
+/// Every section can be left out, missing values fall back to their defaults.
+///
+/// ```ron
+/// (
+///     db: (
+///         path: "db/VRCX.sqlite3",
+///         mode: Immutable,
+///         pool_size: 8,
+///     ),
+///     exclude: (
+///         ids: ["usr_c2a23c47-1622-4b7a-90a4-b824fcaacc69"],
+///     ),
+///     time: (
+///         weekdays: ["Sat", "Sun"],
+///         hours: Some((20, 2)),
+///         timezone: "Europe/Paris",
+///     ),
+/// )
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub db: DbConfig,
+    pub exclude: ExclusionList,
+    pub time: TimeFilter,
+}
+
+impl Config {
+    /// Read the config file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Config`] if the file can't be read or isn't valid RON.
+    #[inline]
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| Error::Config(format!("{}: {err}", path.display())))?;
+
+        ron::from_str(&contents).map_err(|err| Error::Config(format!("{}: {err}", path.display())))
+    }
+
+    /// Read the config file at `path`, or at [`DEFAULT_CONFIG_PATH`] if there is one, and fall
+    /// back to the defaults otherwise.
+    ///
+    /// # Errors
+    ///
+    /// See [`Config::load`].
+    #[inline]
+    pub fn load_or_default(path: Option<&Path>) -> Result<Self> {
+        match path {
+            Some(path) => Self::load(path),
+            None if Path::new(DEFAULT_CONFIG_PATH).is_file() => {
+                Self::load(Path::new(DEFAULT_CONFIG_PATH))
+            }
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Weekday;
+
+    use crate::zaphkiel::config::Config;
+    use crate::zaphkiel::db::{DbConfig, DbMode};
+
+    #[test]
+    fn test_partial_config_falls_back_to_defaults() {
+        let config: Config = ron::from_str("(db: (mode: Immutable, pool_size: 2))").unwrap();
+        let expected = DbConfig {
+            mode: DbMode::Immutable,
+            pool_size: 2,
+            ..DbConfig::default()
+        };
+        assert_eq!(config.db, expected);
+    }
+
+    #[test]
+    fn test_empty_config_is_default() {
+        let config: Config = ron::from_str("()").unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn test_time_section() {
+        let config: Config = ron::from_str(
+            r#"(time: (weekdays: ["Sat", "Sun"], hours: Some((20, 2)), timezone: "Europe/Paris"))"#,
+        )
+        .unwrap();
+        assert_eq!(config.time.weekdays, [Weekday::Sat, Weekday::Sun]);
+        assert_eq!(config.time.timezone, chrono_tz::Europe::Paris);
+        assert!(config.time.since.is_none());
+    }
+}
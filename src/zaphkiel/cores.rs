@@ -0,0 +1,340 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
+
+use serde::{Deserialize, Serialize};
+
+use crate::zaphkiel::community::Communities;
+
+/// Which edges and cliques [`cores`] keeps.
+///
+/// - `min_weight`: edges lighter than this are left out before anything else.
+/// - `min_clique`: smallest clique worth reporting, pairs are cliques too but say nothing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoreOptions {
+    pub min_weight: f64,
+    pub min_clique: usize,
+}
+
+impl Default for CoreOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            min_weight: 0_f64,
+            min_clique: 3,
+        }
+    }
+}
+
+/// The tight inner part of a circle, found inside the circle only.
+///
+/// - `name`: name of the circle in `communities`.
+/// - `k`: highest `k` for which the circle has a k-core, everyone in `core` is linked to at
+///   least `k` others of `core`.
+/// - `core`: members of that k-core, in the order of the circle's members.
+/// - `cliques`: maximal cliques of at least `min_clique` members, largest and heaviest first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CircleCore<N> {
+    pub name: String,
+    pub k: usize,
+    pub core: Vec<N>,
+    pub cliques: Vec<Vec<N>>,
+}
+
+/// Result of [`cores`].
+///
+/// - `min_weight`: the threshold the edges passed.
+/// - `core_numbers`: the highest k-core of the whole graph everyone is in, highest first.
+/// - `circles`: the inner core of every circle, in the order of `communities`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cores<N> {
+    pub min_weight: f64,
+    pub core_numbers: Vec<(N, usize)>,
+    pub circles: Vec<CircleCore<N>>,
+}
+
+/// Indexed adjacency lists, sorted so the results don't depend on hashing.
+type Neighbours = Vec<BTreeSet<usize>>;
+
+/// k-core decomposition and maximal cliques of an undirected graph, for the whole graph and
+/// inside every circle of `communities`.
+///
+/// `weight` gives the weight of an edge, edges under `options.min_weight` are ignored. Nodes are
+/// ordered by `Ord`, so the result is deterministic for a given graph.
+#[must_use]
+#[inline]
+pub fn cores<N, F, S1, S2>(
+    adjacency: &HashMap<N, HashSet<N, S2>, S1>,
+    weight: F,
+    communities: &Communities<N>,
+    options: &CoreOptions,
+) -> Cores<N>
+where
+    N: Clone + Eq + Hash + Ord,
+    F: Fn(&N, &N) -> f64,
+    S1: std::hash::BuildHasher,
+    S2: std::hash::BuildHasher,
+{
+    let mut nodes = adjacency.keys().collect::<Vec<_>>();
+    nodes.sort();
+    let indices = nodes
+        .iter()
+        .enumerate()
+        .map(|(idx, node)| (*node, idx))
+        .collect::<HashMap<_, _>>();
+    let neighbours: Neighbours = nodes
+        .iter()
+        .map(|node| {
+            adjacency[*node]
+                .iter()
+                .filter(|other| *other != *node && weight(node, other) >= options.min_weight)
+                .filter_map(|other| indices.get(other).copied())
+                .collect()
+        })
+        .collect();
+
+    let mut core_numbers = core_numbers_of(&neighbours)
+        .into_iter()
+        .enumerate()
+        .map(|(idx, k)| (nodes[idx].clone(), k))
+        .collect::<Vec<_>>();
+    core_numbers.sort_by(|(a, a_k), (b, b_k)| b_k.cmp(a_k).then_with(|| a.cmp(b)));
+
+    let circles = communities
+        .communities
+        .iter()
+        .map(|community| {
+            let members = community
+                .members
+                .iter()
+                .filter_map(|member| indices.get(member).copied())
+                .collect::<Vec<_>>();
+            // the circle on its own, indexed by position in `members`
+            let local_indices = members
+                .iter()
+                .enumerate()
+                .map(|(idx, node)| (*node, idx))
+                .collect::<HashMap<_, _>>();
+            let local: Neighbours = members
+                .iter()
+                .map(|node| {
+                    neighbours[*node]
+                        .iter()
+                        .filter_map(|other| local_indices.get(other).copied())
+                        .collect()
+                })
+                .collect();
+
+            let numbers = core_numbers_of(&local);
+            let k = numbers.iter().copied().max().unwrap_or(0);
+            let core = members
+                .iter()
+                .zip(&numbers)
+                .filter(|(_, it)| **it == k)
+                .map(|(node, _)| nodes[*node].clone())
+                .collect();
+
+            let clique_weight = |clique: &[usize]| {
+                clique
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, a)| clique[i + 1..].iter().map(move |b| (a, b)))
+                    .map(|(a, b)| weight(nodes[members[*a]], nodes[members[*b]]))
+                    .sum::<f64>()
+            };
+            let mut cliques = maximal_cliques(&local, options.min_clique)
+                .into_iter()
+                .map(|clique| {
+                    let weight = clique_weight(&clique);
+                    (clique, weight)
+                })
+                .collect::<Vec<_>>();
+            cliques.sort_by(|(a, a_weight), (b, b_weight)| {
+                b.len()
+                    .cmp(&a.len())
+                    .then_with(|| b_weight.total_cmp(a_weight))
+                    .then_with(|| a.cmp(b))
+            });
+
+            CircleCore {
+                name: community.name.clone(),
+                k,
+                core,
+                cliques: cliques
+                    .into_iter()
+                    .map(|(clique, _)| {
+                        clique
+                            .into_iter()
+                            .map(|it| nodes[members[it]].clone())
+                            .collect()
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    Cores {
+        min_weight: options.min_weight,
+        core_numbers,
+        circles,
+    }
+}
+
+/// Batagelj and Zaversnik's peeling: keep removing the node with the fewest neighbours left, its
+/// core number is the highest such count seen so far.
+fn core_numbers_of(neighbours: &Neighbours) -> Vec<usize> {
+    let mut degrees = neighbours.iter().map(BTreeSet::len).collect::<Vec<_>>();
+    let mut queue = degrees
+        .iter()
+        .enumerate()
+        .map(|(node, degree)| (*degree, node))
+        .collect::<BTreeSet<_>>();
+    let mut removed = vec![false; neighbours.len()];
+    let mut numbers = vec![0; neighbours.len()];
+
+    let mut k = 0;
+    while let Some((degree, node)) = queue.pop_first() {
+        k = k.max(degree);
+        numbers[node] = k;
+        removed[node] = true;
+        for &other in &neighbours[node] {
+            if removed[other] {
+                continue;
+            }
+            queue.remove(&(degrees[other], other));
+            degrees[other] -= 1;
+            queue.insert((degrees[other], other));
+        }
+    }
+
+    numbers
+}
+
+/// Bron-Kerbosch with pivoting, only the cliques of at least `min_size` nodes, every clique
+/// sorted.
+fn maximal_cliques(neighbours: &Neighbours, min_size: usize) -> Vec<Vec<usize>> {
+    let mut cliques = vec![];
+    bron_kerbosch(
+        neighbours,
+        &mut vec![],
+        (0..neighbours.len()).collect(),
+        BTreeSet::new(),
+        min_size,
+        &mut cliques,
+    );
+    cliques
+}
+
+fn bron_kerbosch(
+    neighbours: &Neighbours,
+    clique: &mut Vec<usize>,
+    mut candidates: BTreeSet<usize>,
+    mut excluded: BTreeSet<usize>,
+    min_size: usize,
+    cliques: &mut Vec<Vec<usize>>,
+) {
+    if candidates.is_empty() && excluded.is_empty() {
+        if clique.len() >= min_size {
+            let mut clique = clique.clone();
+            clique.sort_unstable();
+            cliques.push(clique);
+        }
+        return;
+    }
+    // too few candidates left to ever get big enough
+    if clique.len() + candidates.len() < min_size {
+        return;
+    }
+
+    let Some(pivot) = candidates
+        .iter()
+        .chain(&excluded)
+        .max_by_key(|it| neighbours[**it].intersection(&candidates).count())
+        .copied()
+    else {
+        return;
+    };
+    let rest = candidates
+        .difference(&neighbours[pivot])
+        .copied()
+        .collect::<Vec<_>>();
+    for node in rest {
+        clique.push(node);
+        bron_kerbosch(
+            neighbours,
+            clique,
+            candidates
+                .intersection(&neighbours[node])
+                .copied()
+                .collect(),
+            excluded.intersection(&neighbours[node]).copied().collect(),
+            min_size,
+            cliques,
+        );
+        clique.pop();
+        candidates.remove(&node);
+        excluded.insert(node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use crate::zaphkiel::community::{Communities, Community};
+    use crate::zaphkiel::cores::{cores, CoreOptions};
+
+    #[test]
+    fn test_inner_core_of_a_loose_circle() {
+        // a, b, c and d all know each other, e only knows a and f only knows e
+        let edges = [
+            ("a", "b", 5_f64),
+            ("a", "c", 5_f64),
+            ("a", "d", 5_f64),
+            ("b", "c", 5_f64),
+            ("b", "d", 5_f64),
+            ("c", "d", 1_f64),
+            ("a", "e", 5_f64),
+            ("e", "f", 5_f64),
+        ];
+        let mut adjacency: HashMap<&str, HashSet<&str>> = HashMap::new();
+        for (a, b, _) in edges {
+            adjacency.entry(a).or_default().insert(b);
+            adjacency.entry(b).or_default().insert(a);
+        }
+        let weight = |a: &&str, b: &&str| {
+            edges
+                .iter()
+                .find(|(x, y, _)| (x, y) == (a, b) || (x, y) == (b, a))
+                .map_or(0_f64, |(_, _, weight)| *weight)
+        };
+        let communities = Communities {
+            resolution: 1_f64,
+            modularity: 0_f64,
+            communities: vec![Community {
+                name: "a's circle".to_string(),
+                members: vec!["a", "b", "c", "d", "e", "f"],
+                internal_weight: 31_f64,
+                cohesion: 1_f64,
+                density: 0.5,
+            }],
+        };
+
+        let found = cores(&adjacency, weight, &communities, &CoreOptions::default());
+        assert_eq!(found.core_numbers[0], ("a", 3));
+        assert_eq!(found.circles[0].k, 3);
+        assert_eq!(found.circles[0].core, vec!["a", "b", "c", "d"]);
+        assert_eq!(found.circles[0].cliques, vec![vec!["a", "b", "c", "d"]]);
+
+        // without the light edge between c and d the clique breaks in two triangles
+        let options = CoreOptions {
+            min_weight: 2_f64,
+            ..CoreOptions::default()
+        };
+        let found = cores(&adjacency, weight, &communities, &options);
+        assert_eq!(found.circles[0].k, 2);
+        assert_eq!(
+            found.circles[0].cliques,
+            vec![vec!["a", "b", "c"], vec!["a", "b", "d"]]
+        );
+    }
+}
@@ -1,16 +1,185 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::time::Duration;
 
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteConnectOptions;
 use sqlx::{Sqlite, SqlitePool};
 
 use crate::zaphkiel::cpu_info::CPU_THREADS;
+use crate::zaphkiel::error::{Error, Result};
 
-#[allow(clippy::missing_panics_doc)]
+pub const ENV_DB_PATH: &str = "VRCX_INSIGHTS_DB";
+pub const ENV_DB_MODE: &str = "VRCX_INSIGHTS_DB_MODE";
+pub const ENV_DB_POOL_SIZE: &str = "VRCX_INSIGHTS_DB_POOL_SIZE";
+pub const ENV_DB_ACQUIRE_TIMEOUT: &str = "VRCX_INSIGHTS_DB_ACQUIRE_TIMEOUT";
+pub const ENV_DB_BUSY_TIMEOUT: &str = "VRCX_INSIGHTS_DB_BUSY_TIMEOUT";
+
+/// How the database file is opened.
+///
+/// - `ReadOnly`: regular read only connection, `SQLite` still takes shared locks so a VRCX that is
+///   writing at the same time can make queries wait for `busy_timeout_secs`.
+/// - `Immutable`: tells `SQLite` the file can't change, which skips locking entirely. Only use this
+///   on a copy of the database or while VRCX is closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DbMode {
+    #[default]
+    ReadOnly,
+    Immutable,
+}
+
+impl FromStr for DbMode {
+    type Err = Error;
+
+    #[inline]
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ro" | "read-only" | "readonly" | "read_only" => Ok(Self::ReadOnly),
+            "immutable" => Ok(Self::Immutable),
+            _ => Err(Error::Config(format!("unknown database mode: {s}"))),
+        }
+    }
+}
+
+/// Everything needed to open the VRCX database.
+///
+/// Values are layered from lowest to highest priority: [`DbConfig::default`], the `db` section of
+/// the config file, the `VRCX_INSIGHTS_DB*` environment variables ([`DbConfig::with_env`]) and
+/// finally the command line flags.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(clippy::module_name_repetitions)]
+pub struct DbConfig {
+    pub path: PathBuf,
+    pub mode: DbMode,
+    pub pool_size: u32,
+    pub acquire_timeout_secs: u64,
+    pub busy_timeout_secs: u64,
+}
+
+impl Default for DbConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            path: "db/VRCX.sqlite3".into(),
+            mode: DbMode::default(),
+            pool_size: u32::try_from(CPU_THREADS).unwrap_or(u32::MAX),
+            acquire_timeout_secs: 60 * 60,
+            busy_timeout_secs: 5,
+        }
+    }
+}
+
+impl DbConfig {
+    #[must_use]
+    #[inline]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Override the values which have their `VRCX_INSIGHTS_DB*` environment variable set.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Config`] if one of the variables can't be parsed.
+    #[inline]
+    pub fn with_env(mut self) -> Result<Self> {
+        if let Some(path) = std::env::var_os(ENV_DB_PATH) {
+            self.path = path.into();
+        }
+        if let Some(mode) = env_var(ENV_DB_MODE)? {
+            self.mode = mode;
+        }
+        if let Some(pool_size) = env_var(ENV_DB_POOL_SIZE)? {
+            self.pool_size = pool_size;
+        }
+        if let Some(acquire_timeout) = env_var(ENV_DB_ACQUIRE_TIMEOUT)? {
+            self.acquire_timeout_secs = acquire_timeout;
+        }
+        if let Some(busy_timeout) = env_var(ENV_DB_BUSY_TIMEOUT)? {
+            self.busy_timeout_secs = busy_timeout;
+        }
+
+        Ok(self)
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn connect_options(&self) -> SqliteConnectOptions {
+        SqliteConnectOptions::new()
+            .filename(&self.path)
+            .read_only(true)
+            .immutable(self.mode == DbMode::Immutable)
+            .create_if_missing(false)
+            .busy_timeout(Duration::from_secs(self.busy_timeout_secs))
+    }
+}
+
+fn env_var<T>(key: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match std::env::var(key) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|err| Error::Config(format!("{key}={value}: {err}"))),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(err) => Err(Error::Config(format!("{key}: {err}"))),
+    }
+}
+
+/// `SQLITE_BUSY` and `SQLITE_LOCKED`, including their extended result codes.
+fn is_locked(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Database(err) => err
+            .code()
+            .and_then(|code| code.parse::<i32>().ok())
+            .is_some_and(|code| matches!(code & 0xff, 5 | 6)),
+        _ => false,
+    }
+}
+
+/// Open a connection pool to the database described by `config`.
+///
+/// A single probe query is run so a missing table or a locked file is reported here instead of
+/// halfway through an analysis.
+///
+/// # Errors
+///
+/// Returns [`Error::DbNotFound`] or [`Error::DbLocked`] if the file is missing or locked by a
+/// running VRCX, and [`Error::Sqlx`] for anything else.
 #[inline]
-pub async fn establish_connection() -> SqlitePool {
-    sqlx::pool::PoolOptions::<Sqlite>::new()
-        .acquire_timeout(Duration::from_secs(60 * 60))
-        .max_connections(u32::try_from(CPU_THREADS).unwrap())
-        .connect("sqlite://db/VRCX.sqlite3?mode=ro")
+pub async fn establish_connection(config: &DbConfig) -> Result<SqlitePool> {
+    if !Path::new(&config.path).is_file() {
+        return Err(Error::DbNotFound(config.path.clone()));
+    }
+
+    let map_err = |err: sqlx::Error| {
+        if is_locked(&err) {
+            Error::DbLocked(config.path.clone())
+        } else {
+            Error::Sqlx(err)
+        }
+    };
+
+    let pool = sqlx::pool::PoolOptions::<Sqlite>::new()
+        .acquire_timeout(Duration::from_secs(config.acquire_timeout_secs))
+        .max_connections(config.pool_size.max(1))
+        .connect_with(config.connect_options())
         .await
-        .unwrap()
+        .map_err(map_err)?;
+
+    sqlx::query("select 1 from gamelog_join_leave limit 1")
+        .fetch_optional(&pool)
+        .await
+        .map_err(map_err)?;
+
+    Ok(pool)
 }
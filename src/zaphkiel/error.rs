@@ -0,0 +1,178 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+use crate::zaphkiel::ids::{Id, Name};
+use crate::zaphkiel::world_instance::WorldInstanceParseError;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Everything that can go wrong in the library.
+///
+/// - `DbNotFound`: there is no database file at the configured path.
+/// - `DbLocked`: the database is locked, usually because VRCX is running and writing to it.
+/// - `Config`: the configuration (file, environment or flags) is invalid.
+/// - `Sqlx`: any other error reported by `SQLite`.
+/// - `Io`: reading or writing a file failed.
+/// - `Ron`: (de)serializing a RON file failed.
+/// - `Json`: (de)serializing JSON failed.
+/// - `UnknownUser`: the user id doesn't appear in the database.
+/// - `UnknownName`: the display name doesn't appear in the database.
+/// - `AmbiguousName`: more than one user has used the display name, with all of them.
+/// - `MissingTable`: a table VRCX only creates for logged in users doesn't exist.
+/// - `MalformedRow`: a row of `gamelog_join_leave` couldn't be parsed.
+/// - `Parse`: a value outside a row couldn't be parsed.
+/// - `Task`: a spawned query task panicked or was cancelled.
+#[derive(Debug)]
+pub enum Error {
+    DbNotFound(PathBuf),
+    DbLocked(PathBuf),
+    Config(String),
+    Sqlx(sqlx::Error),
+    Io(std::io::Error),
+    Ron(String),
+    Json(String),
+    UnknownUser(Id),
+    UnknownName(Name),
+    AmbiguousName(Name, Vec<Id>),
+    MissingTable(String),
+    MalformedRow { id: i64, source: ParseError },
+    Parse(ParseError),
+    Task(String),
+}
+
+impl Display for Error {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DbNotFound(path) => write!(f, "database not found at {}", path.display()),
+            Self::DbLocked(path) => write!(
+                f,
+                "database at {} is locked, close VRCX or copy the file somewhere else",
+                path.display()
+            ),
+            Self::Config(message) => write!(f, "invalid config: {message}"),
+            Self::Sqlx(err) => write!(f, "database error: {err}"),
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Ron(err) => write!(f, "ron error: {err}"),
+            Self::Json(err) => write!(f, "json error: {err}"),
+            Self::UnknownUser(id) => write!(f, "no user with id {id} in the database"),
+            Self::UnknownName(name) => write!(f, "no user named {name} in the database"),
+            Self::AmbiguousName(name, candidates) => {
+                write!(f, "{} users have been named {name}:", candidates.len())?;
+                for candidate in candidates {
+                    write!(f, " {candidate}")?;
+                }
+                Ok(())
+            }
+            Self::MissingTable(table) => write!(
+                f,
+                "table {table} doesn't exist, has VRCX been used while logged in as the owner?"
+            ),
+            Self::MalformedRow { id, source } => {
+                write!(f, "malformed gamelog_join_leave row {id}: {source}")
+            }
+            Self::Parse(err) => err.fmt(f),
+            Self::Task(err) => write!(f, "query task failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    #[inline]
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Sqlx(err) => Some(err),
+            Self::Io(err) => Some(err),
+            Self::MalformedRow { source, .. } | Self::Parse(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    #[inline]
+    fn from(err: sqlx::Error) -> Self {
+        Self::Sqlx(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    #[inline]
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<ron::Error> for Error {
+    #[inline]
+    fn from(err: ron::Error) -> Self {
+        Self::Ron(err.to_string())
+    }
+}
+
+impl From<ron::error::SpannedError> for Error {
+    #[inline]
+    fn from(err: ron::error::SpannedError) -> Self {
+        Self::Ron(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    #[inline]
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err.to_string())
+    }
+}
+
+impl From<ParseError> for Error {
+    #[inline]
+    fn from(err: ParseError) -> Self {
+        Self::Parse(err)
+    }
+}
+
+impl From<tokio::task::JoinError> for Error {
+    #[inline]
+    fn from(err: tokio::task::JoinError) -> Self {
+        Self::Task(err.to_string())
+    }
+}
+
+/// A value read from the database that doesn't look like what VRCX writes.
+///
+/// - `UnknownRegion`: the `region(...)` of an instance isn't a known region.
+/// - `UnknownGroupAccessType`: the `groupAccessType(...)` of an instance isn't known.
+/// - `UnknownJoinLeaveEvent`: the `type` column isn't a join or a leave.
+/// - `InvalidTimestamp`: the `created_at` column isn't an RFC 3339 timestamp.
+/// - `WorldInstance`: the instance string couldn't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+pub enum ParseError {
+    UnknownRegion(String),
+    UnknownGroupAccessType(String),
+    UnknownJoinLeaveEvent(String),
+    InvalidTimestamp(String),
+    WorldInstance(WorldInstanceParseError),
+}
+
+impl Display for ParseError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownRegion(value) => write!(f, "unknown region: {value}"),
+            Self::UnknownGroupAccessType(value) => write!(f, "unknown group access type: {value}"),
+            Self::UnknownJoinLeaveEvent(value) => write!(f, "unknown join/leave event: {value}"),
+            Self::InvalidTimestamp(value) => write!(f, "invalid timestamp: {value}"),
+            Self::WorldInstance(err) => write!(f, "invalid world instance: {err:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<WorldInstanceParseError> for ParseError {
+    #[inline]
+    fn from(err: WorldInstanceParseError) -> Self {
+        Self::WorldInstance(err)
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::zaphkiel::gamelog_join_leave::GamelogJoinLeave;
+use crate::zaphkiel::ids::Id;
+
+/// People and worlds left out of every query, the `exclude` section of the config file.
+///
+/// - `ids`: user ids.
+/// - `names`: display name patterns, case insensitive, `*` matches any run of characters and `?`
+///   a single one. They're matched against the name in each row, so someone who was renamed is
+///   only left out while their name matched.
+/// - `worlds`: world ids, `wrld_...`.
+///
+/// ```ron
+/// (
+///     exclude: (
+///         ids: ["usr_c2a23c47-1622-4b7a-90a4-b824fcaacc69"],
+///         names: ["*bot*"],
+///         worlds: ["wrld_4432ea9b-729c-46e3-8eaf-846aa0a37fdd"],
+///     ),
+/// )
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExclusionList {
+    pub ids: BTreeSet<Id>,
+    pub names: Vec<String>,
+    pub worlds: BTreeSet<String>,
+}
+
+impl ExclusionList {
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty() && self.names.is_empty() && self.worlds.is_empty()
+    }
+
+    /// Whether the user with `user_id`, going by `display_name`, is excluded.
+    #[must_use]
+    #[inline]
+    pub fn excludes_user(&self, user_id: &Id, display_name: &str) -> bool {
+        self.ids.contains(user_id)
+            || self
+                .names
+                .iter()
+                .any(|pattern| matches_pattern(pattern, display_name))
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn excludes_world(&self, world_id: &str) -> bool {
+        self.worlds.contains(world_id)
+    }
+}
+
+/// Who and what [`ExclusionList`] left out, written to `exclusions.ron`.
+///
+/// - `users`: excluded users that were seen, with the last name they were seen with.
+/// - `worlds`: excluded worlds that were seen, with how many rows were left out in each.
+/// - `rows`: number of rows left out, rows read by several queries are counted every time.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(clippy::module_name_repetitions)]
+pub struct ExclusionReport {
+    pub users: BTreeMap<Id, Arc<str>>,
+    pub worlds: BTreeMap<String, u64>,
+    pub rows: u64,
+}
+
+impl ExclusionReport {
+    /// Record `row` if `exclusions` leaves it out, returns whether it did.
+    #[inline]
+    pub fn record(&mut self, exclusions: &ExclusionList, row: &GamelogJoinLeave) -> bool {
+        let world = row
+            .location
+            .as_ref()
+            .map(|location| location.world_id.as_str())
+            .filter(|world_id| exclusions.excludes_world(world_id));
+        let user = row
+            .user_id
+            .as_ref()
+            .map(Id::from)
+            .filter(|user_id| exclusions.excludes_user(user_id, &row.display_name));
+
+        if let Some(world) = world {
+            *self.worlds.entry(world.to_string()).or_default() += 1;
+        }
+        if let Some(user) = &user {
+            self.users.insert(user.clone(), row.display_name.clone());
+        }
+        let excluded = world.is_some() || user.is_some();
+        if excluded {
+            self.rows += 1;
+        }
+
+        excluded
+    }
+
+    /// Add up two reports, the names of `other` win.
+    #[inline]
+    pub fn merge(&mut self, other: Self) {
+        self.users.extend(other.users);
+        for (world, rows) in other.worlds {
+            *self.worlds.entry(world).or_default() += rows;
+        }
+        self.rows += other.rows;
+    }
+}
+
+/// Case insensitive glob match supporting `*` and `?`.
+fn matches_pattern(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase().chars().collect::<Vec<_>>();
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+
+    let (mut p, mut t) = (0, 0);
+    // position of the last `*` in the pattern and of the text it's matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::zaphkiel::exclusion::{matches_pattern, ExclusionList, ExclusionReport};
+    use crate::zaphkiel::gamelog_join_leave::GamelogJoinLeave;
+
+    #[test]
+    fn test_patterns() {
+        assert!(matches_pattern("*bot*", "Music Bot 3000"));
+        assert!(matches_pattern("kat", "KAT"));
+        assert!(matches_pattern("k?t", "kit"));
+        assert!(matches_pattern("a*b*c", "aXbYbZc"));
+        assert!(!matches_pattern("kat", "kate"));
+        assert!(!matches_pattern("*bot", "bots"));
+    }
+
+    #[test]
+    fn test_report_records_excluded_rows() {
+        let exclusions: ExclusionList =
+            ron::from_str(r#"(ids: ["usr_a"], worlds: ["wrld_x"])"#).unwrap();
+        let row = |user_id: &str, location: &str| GamelogJoinLeave {
+            display_name: user_id.into(),
+            user_id: Some(user_id.into()),
+            location: location.parse().ok(),
+            ..GamelogJoinLeave::new()
+        };
+
+        let mut report = ExclusionReport::default();
+        assert!(report.record(&exclusions, &row("usr_a", "wrld_y:1")));
+        assert!(report.record(&exclusions, &row("usr_b", "wrld_x:1")));
+        assert!(!report.record(&exclusions, &row("usr_b", "wrld_y:1")));
+        assert_eq!(report.rows, 2);
+        assert_eq!(report.users.keys().collect::<Vec<_>>(), [&"usr_a".into()]);
+        assert_eq!(report.worlds["wrld_x"], 1);
+    }
+}
@@ -0,0 +1,103 @@
+// create table <user>_friend_log_history
+// (
+// id                    INTEGER
+// primary key,
+// created_at            TEXT,
+// type                  TEXT,
+// user_id               TEXT,
+// display_name          TEXT,
+// previous_display_name TEXT,
+// trust_level           TEXT,
+// previous_trust_level  TEXT,
+// friend_number         INTEGER
+// );
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use sqlx::types::chrono::{DateTime, Utc};
+
+use crate::zaphkiel::error::ParseError;
+use crate::zaphkiel::ids::Id;
+
+#[derive(Debug, sqlx::FromRow, Clone)]
+#[allow(clippy::module_name_repetitions)]
+pub struct FriendLogHistoryRow {
+    pub id: i64,
+    pub created_at: String,
+    pub r#type: Option<String>,
+    pub user_id: String,
+    pub display_name: Option<String>,
+}
+
+/// Type of a friend log entry, VRCX also logs display name and trust level changes and friend
+/// requests, those are all `Other`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(clippy::module_name_repetitions)]
+pub enum FriendLogEvent {
+    Friend,
+    Unfriend,
+    Other,
+}
+
+impl From<&str> for FriendLogEvent {
+    #[inline]
+    fn from(value: &str) -> Self {
+        match value {
+            "Friend" => Self::Friend,
+            "Unfriend" => Self::Unfriend,
+            _ => Self::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[allow(clippy::module_name_repetitions)]
+pub struct FriendLogHistory {
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub event: FriendLogEvent,
+    pub user_id: Id,
+    pub display_name: Option<Arc<str>>,
+}
+
+impl TryFrom<FriendLogHistoryRow> for FriendLogHistory {
+    type Error = ParseError;
+
+    #[inline]
+    fn try_from(row: FriendLogHistoryRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.id,
+            created_at: row
+                .created_at
+                .parse()
+                .map_err(|_| ParseError::InvalidTimestamp(row.created_at))?,
+            event: row
+                .r#type
+                .as_deref()
+                .map_or(FriendLogEvent::Other, FriendLogEvent::from),
+            user_id: row.user_id.into(),
+            display_name: row.display_name.map(Into::into),
+        })
+    }
+}
+
+/// Everyone whose last friend or unfriend event is an unfriend, with when it happened.
+#[must_use]
+#[inline]
+pub fn former_friends(history: &[FriendLogHistory]) -> HashMap<Id, DateTime<Utc>> {
+    let mut sorted = history.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|entry| (entry.created_at, entry.id));
+
+    let mut last: HashMap<Id, &FriendLogHistory> = HashMap::new();
+    for entry in sorted {
+        if entry.event != FriendLogEvent::Other {
+            last.insert(entry.user_id.clone(), entry);
+        }
+    }
+
+    last.into_iter()
+        .filter(|(_, entry)| entry.event == FriendLogEvent::Unfriend)
+        .map(|(user_id, entry)| (user_id, entry.created_at))
+        .collect()
+}
@@ -0,0 +1,582 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::hash::Hash;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::zaphkiel::co_presence::CoPresence;
+use crate::zaphkiel::error::Error;
+use crate::zaphkiel::metadata::metadata_of;
+use crate::zaphkiel::weight::WeightMode;
+
+/// Everything known about how much user `a` spends time with user `b`.
+///
+/// - `count`, `hours`, `days`: see [`CoPresence`].
+/// - `percentage`, `percentile`: see [`crate::zaphkiel::metadata::Metadata`], relative to `a`.
+/// - `reciprocity`: `min / max` of the percentages of `a -> b` and `b -> a`, `1` when both spend
+///   the same share of their time with each other. `0` when `b` hasn't been queried.
+/// - `z_score`: how far `ln(1 + crowd hours)` stands out from everyone else `a` was seen with,
+///   where crowd hours are hours divided by how many other people were in the instances on
+///   average (see [`CoPresence::crowd_seconds`]). An afternoon in a packed public world says
+///   little, the same afternoon in an instance of three says a lot.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct PairStats {
+    pub count: u32,
+    pub hours: f64,
+    pub days: u32,
+    pub percentage: f64,
+    pub percentile: f64,
+    pub reciprocity: f64,
+    pub z_score: f64,
+}
+
+/// [`PairStats`] of every edge of `graph`, percentages are computed with `mode`.
+#[must_use]
+#[inline]
+#[allow(clippy::cast_precision_loss)]
+pub fn pair_stats<N>(
+    graph: &HashMap<N, HashMap<N, CoPresence>>,
+    mode: WeightMode,
+) -> HashMap<N, HashMap<N, PairStats>>
+where
+    N: Clone + Eq + Hash,
+{
+    let mut ret = graph
+        .iter()
+        .map(|(node, edges)| {
+            let log_hours = edges
+                .values()
+                .map(|co_presence| crowd_hours(co_presence).ln_1p())
+                .collect::<Vec<_>>();
+            let n = log_hours.len() as f64;
+            let mean = log_hours.iter().sum::<f64>() / n;
+            let std = (log_hours.iter().map(|it| (it - mean).powi(2)).sum::<f64>() / n).sqrt();
+
+            let stats = metadata_of(edges, mode)
+                .into_iter()
+                .map(|(other, metadata)| {
+                    let co_presence = &edges[other];
+                    let hours = hours(co_presence);
+                    let z_score = if std > 0_f64 {
+                        (crowd_hours(co_presence).ln_1p() - mean) / std
+                    } else {
+                        0_f64
+                    };
+                    let stats = PairStats {
+                        count: co_presence.overlaps,
+                        hours,
+                        days: co_presence.distinct_days(),
+                        percentage: metadata.percentage,
+                        percentile: metadata.percentile,
+                        reciprocity: 0_f64,
+                        z_score,
+                    };
+                    (other.clone(), stats)
+                })
+                .collect::<HashMap<_, _>>();
+            (node.clone(), stats)
+        })
+        .collect::<HashMap<_, _>>();
+
+    let percentages = ret
+        .iter()
+        .flat_map(|(node, edges)| {
+            edges
+                .iter()
+                .map(move |(other, stats)| ((node.clone(), other.clone()), stats.percentage))
+        })
+        .collect::<HashMap<_, _>>();
+    for (node, edges) in &mut ret {
+        for (other, stats) in edges {
+            if let Some(theirs) = percentages.get(&(other.clone(), node.clone())) {
+                let (low, high) = if *theirs < stats.percentage {
+                    (*theirs, stats.percentage)
+                } else {
+                    (stats.percentage, *theirs)
+                };
+                stats.reciprocity = if high > 0_f64 { low / high } else { 0_f64 };
+            }
+        }
+    }
+
+    ret
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn hours(co_presence: &CoPresence) -> f64 {
+    co_presence.seconds as f64 / 3600_f64
+}
+
+fn crowd_hours(co_presence: &CoPresence) -> f64 {
+    co_presence.crowd_seconds / 3600_f64
+}
+
+/// Decides whether two users are friends from their [`PairStats`].
+pub trait FriendshipClassifier {
+    /// How strongly the pair looks like friends, only comparable within the same classifier.
+    fn score(&self, stats: &PairStats) -> f64;
+
+    fn is_friend(&self, stats: &PairStats) -> bool;
+
+    /// Tune the classifier to a set of pairs known to be friends (`true`) or not (`false`).
+    ///
+    /// Does nothing unless there is at least one sample of each.
+    fn calibrate(&mut self, samples: &[(PairStats, bool)]);
+}
+
+/// The number of right and wrong guesses of a classifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Confusion {
+    pub true_positives: u32,
+    pub false_positives: u32,
+    pub false_negatives: u32,
+    pub true_negatives: u32,
+}
+
+impl Confusion {
+    #[must_use]
+    #[inline]
+    pub fn of<F>(samples: &[(PairStats, bool)], predict: F) -> Self
+    where
+        F: Fn(&PairStats) -> bool,
+    {
+        let mut ret = Self::default();
+        for (stats, actual) in samples {
+            match (predict(stats), actual) {
+                (true, true) => ret.true_positives += 1,
+                (true, false) => ret.false_positives += 1,
+                (false, true) => ret.false_negatives += 1,
+                (false, false) => ret.true_negatives += 1,
+            }
+        }
+        ret
+    }
+
+    /// Share of the guessed friends that are friends.
+    #[must_use]
+    #[inline]
+    pub fn precision(&self) -> f64 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_positives,
+        )
+    }
+
+    /// Share of the friends that were guessed.
+    #[must_use]
+    #[inline]
+    pub fn recall(&self) -> f64 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_negatives,
+        )
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn f1(&self) -> f64 {
+        let (precision, recall) = (self.precision(), self.recall());
+        if precision + recall > 0_f64 {
+            2_f64 * precision * recall / (precision + recall)
+        } else {
+            0_f64
+        }
+    }
+}
+
+fn ratio(a: u32, b: u32) -> f64 {
+    if b == 0 {
+        0_f64
+    } else {
+        f64::from(a) / f64::from(b)
+    }
+}
+
+fn has_both_classes(samples: &[(PairStats, bool)]) -> bool {
+    samples.iter().any(|(_, it)| *it) && samples.iter().any(|(_, it)| !*it)
+}
+
+/// The threshold out of `candidates` with the best F1 score, ties go to the first one.
+fn best_threshold<F>(samples: &[(PairStats, bool)], candidates: &[f64], predict: F) -> Option<f64>
+where
+    F: Fn(&PairStats, f64) -> bool,
+{
+    let mut best: Option<(f64, f64)> = None;
+    for candidate in candidates {
+        let f1 = Confusion::of(samples, |stats| predict(stats, *candidate)).f1();
+        if best.is_none_or(|(_, best)| f1 > best) {
+            best = Some((*candidate, f1));
+        }
+    }
+    best.map(|(threshold, _)| threshold)
+}
+
+/// Every distinct value of `value` in `samples`, sorted.
+fn candidates<F>(samples: &[(PairStats, bool)], value: F) -> Vec<f64>
+where
+    F: Fn(&PairStats) -> f64,
+{
+    let mut ret = samples
+        .iter()
+        .map(|(stats, _)| value(stats))
+        .collect::<Vec<_>>();
+    ret.push(f64::INFINITY);
+    ret.sort_by(f64::total_cmp);
+    ret.dedup();
+    ret
+}
+
+/// Friends if `percentage > min_percentage` or `percentile > min_percentile`, and they've been
+/// together for at least `min_hours` on at least `min_days` days.
+///
+/// The defaults are the original made-up heuristic of the project.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Thresholds {
+    pub min_percentage: f64,
+    pub min_percentile: f64,
+    pub min_hours: f64,
+    pub min_days: u32,
+}
+
+impl Default for Thresholds {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            min_percentage: 0.05,
+            min_percentile: 0.5,
+            min_hours: 0_f64,
+            min_days: 0,
+        }
+    }
+}
+
+impl FriendshipClassifier for Thresholds {
+    #[inline]
+    fn score(&self, stats: &PairStats) -> f64 {
+        (stats.percentage / self.min_percentage).max(stats.percentile / self.min_percentile)
+    }
+
+    #[inline]
+    fn is_friend(&self, stats: &PairStats) -> bool {
+        (stats.percentage > self.min_percentage || stats.percentile > self.min_percentile)
+            && stats.hours >= self.min_hours
+            && stats.days >= self.min_days
+    }
+
+    /// Coordinate search over the values seen in `samples`, starting from nobody being a friend.
+    /// `min_hours` and `min_days` are kept.
+    #[inline]
+    fn calibrate(&mut self, samples: &[(PairStats, bool)]) {
+        if !has_both_classes(samples) {
+            return;
+        }
+
+        self.min_percentage = f64::INFINITY;
+        self.min_percentile = f64::INFINITY;
+
+        let percentages = candidates(samples, |it| it.percentage);
+        let percentiles = candidates(samples, |it| it.percentile);
+        for _ in 0..2 {
+            let current = *self;
+            if let Some(min_percentage) = best_threshold(samples, &percentages, |stats, it| {
+                Thresholds {
+                    min_percentage: it,
+                    ..current
+                }
+                .is_friend(stats)
+            }) {
+                self.min_percentage = min_percentage;
+            }
+            let current = *self;
+            if let Some(min_percentile) = best_threshold(samples, &percentiles, |stats, it| {
+                Thresholds {
+                    min_percentile: it,
+                    ..current
+                }
+                .is_friend(stats)
+            }) {
+                self.min_percentile = min_percentile;
+            }
+        }
+    }
+}
+
+/// Friends if the time they spend together stands out by more than `threshold` standard
+/// deviations, see [`PairStats::z_score`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ZScore {
+    pub threshold: f64,
+}
+
+impl Default for ZScore {
+    #[inline]
+    fn default() -> Self {
+        Self { threshold: 1.5 }
+    }
+}
+
+impl FriendshipClassifier for ZScore {
+    #[inline]
+    fn score(&self, stats: &PairStats) -> f64 {
+        stats.z_score
+    }
+
+    #[inline]
+    fn is_friend(&self, stats: &PairStats) -> bool {
+        stats.z_score >= self.threshold
+    }
+
+    #[inline]
+    fn calibrate(&mut self, samples: &[(PairStats, bool)]) {
+        if !has_both_classes(samples) {
+            return;
+        }
+
+        let z_scores = candidates(samples, |it| it.z_score);
+        if let Some(threshold) = best_threshold(samples, &z_scores, |stats, it| stats.z_score >= it)
+        {
+            self.threshold = threshold;
+        }
+    }
+}
+
+/// Number of features used by [`Logistic`].
+pub const LOGISTIC_FEATURES: usize = 7;
+
+/// Logistic regression over [`Logistic::features`], friends if the probability is at least `0.5`.
+///
+/// The default weights are a hand-made guess, calibrating fits them to the samples.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Logistic {
+    pub intercept: f64,
+    pub weights: [f64; LOGISTIC_FEATURES],
+}
+
+impl Default for Logistic {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            intercept: -6_f64,
+            weights: [0.2, 0.8, 0.8, 2.0, 2.0, 1.0, 0.8],
+        }
+    }
+}
+
+impl Logistic {
+    const ITERATIONS: usize = 2000;
+    const LEARNING_RATE: f64 = 0.1;
+
+    /// `ln(1 + count)`, `ln(1 + hours)`, `ln(1 + days)`, `percentage / 100`,
+    /// `percentile / 100`, `reciprocity` and `z_score`.
+    #[must_use]
+    #[inline]
+    pub fn features(stats: &PairStats) -> [f64; LOGISTIC_FEATURES] {
+        [
+            f64::from(stats.count).ln_1p(),
+            stats.hours.ln_1p(),
+            f64::from(stats.days).ln_1p(),
+            stats.percentage / 100_f64,
+            stats.percentile / 100_f64,
+            stats.reciprocity,
+            stats.z_score,
+        ]
+    }
+
+    fn probability(&self, features: &[f64; LOGISTIC_FEATURES]) -> f64 {
+        let z = self.intercept
+            + self
+                .weights
+                .iter()
+                .zip(features)
+                .map(|(weight, feature)| weight * feature)
+                .sum::<f64>();
+        1_f64 / (1_f64 + (-z).exp())
+    }
+}
+
+impl FriendshipClassifier for Logistic {
+    #[inline]
+    fn score(&self, stats: &PairStats) -> f64 {
+        self.probability(&Self::features(stats))
+    }
+
+    #[inline]
+    fn is_friend(&self, stats: &PairStats) -> bool {
+        self.score(stats) >= 0.5
+    }
+
+    /// Gradient descent from zero, friends are weighted up so they aren't drowned out by the far
+    /// more numerous strangers.
+    #[inline]
+    #[allow(clippy::cast_precision_loss)]
+    fn calibrate(&mut self, samples: &[(PairStats, bool)]) {
+        if !has_both_classes(samples) {
+            return;
+        }
+
+        let positives = samples.iter().filter(|(_, it)| *it).count() as f64;
+        let negatives = samples.len() as f64 - positives;
+        let samples = samples
+            .iter()
+            .map(|(stats, friend)| {
+                let (label, weight) = if *friend {
+                    (1_f64, negatives / positives)
+                } else {
+                    (0_f64, 1_f64)
+                };
+                (Self::features(stats), label, weight)
+            })
+            .collect::<Vec<_>>();
+        let total_weight = samples.iter().map(|(_, _, weight)| weight).sum::<f64>();
+
+        *self = Self {
+            intercept: 0_f64,
+            weights: [0_f64; LOGISTIC_FEATURES],
+        };
+        for _ in 0..Self::ITERATIONS {
+            let mut intercept = 0_f64;
+            let mut weights = [0_f64; LOGISTIC_FEATURES];
+            for (features, label, weight) in &samples {
+                let error = (self.probability(features) - label) * weight;
+                intercept += error;
+                for (gradient, feature) in weights.iter_mut().zip(features) {
+                    *gradient += error * feature;
+                }
+            }
+            self.intercept -= Self::LEARNING_RATE * intercept / total_weight;
+            for (weight, gradient) in self.weights.iter_mut().zip(weights) {
+                *weight -= Self::LEARNING_RATE * gradient / total_weight;
+            }
+        }
+    }
+}
+
+/// The built-in classifiers, with their default parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Classifier {
+    #[default]
+    Thresholds,
+    ZScore,
+    Logistic,
+}
+
+impl Classifier {
+    #[must_use]
+    #[inline]
+    pub fn build(self) -> Box<dyn FriendshipClassifier + Send + Sync> {
+        match self {
+            Self::Thresholds => Box::<Thresholds>::default(),
+            Self::ZScore => Box::<ZScore>::default(),
+            Self::Logistic => Box::<Logistic>::default(),
+        }
+    }
+}
+
+impl FromStr for Classifier {
+    type Err = Error;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace(['-', '_'], "").as_str() {
+            "thresholds" | "threshold" => Ok(Self::Thresholds),
+            "zscore" => Ok(Self::ZScore),
+            "logistic" => Ok(Self::Logistic),
+            _ => Err(Error::Config(format!("unknown classifier: {s}"))),
+        }
+    }
+}
+
+impl Display for Classifier {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Thresholds => "thresholds",
+            Self::ZScore => "z-score",
+            Self::Logistic => "logistic",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::zaphkiel::co_presence::CoPresence;
+    use crate::zaphkiel::friendship::{
+        pair_stats, Confusion, FriendshipClassifier, Logistic, PairStats, Thresholds, ZScore,
+    };
+    use crate::zaphkiel::weight::WeightMode;
+
+    fn stats(hours: f64, percentile: f64, z_score: f64) -> PairStats {
+        PairStats {
+            count: 10,
+            hours,
+            days: 3,
+            percentage: percentile / 10_f64,
+            percentile,
+            reciprocity: 0.5,
+            z_score,
+        }
+    }
+
+    fn samples() -> Vec<(PairStats, bool)> {
+        let mut ret = vec![];
+        for i in 0..10 {
+            let i = f64::from(i);
+            ret.push((stats(20_f64 + i, 60_f64 + i, 2_f64 + i / 10_f64), true));
+            ret.push((stats(i / 10_f64, 1_f64 + i, -1_f64 + i / 10_f64), false));
+        }
+        ret
+    }
+
+    #[test]
+    fn test_legacy_thresholds() {
+        let thresholds = Thresholds::default();
+        assert!(thresholds.is_friend(&stats(0_f64, 0.6, 0_f64)));
+        assert!(!thresholds.is_friend(&stats(0_f64, 0.4, 0_f64)));
+    }
+
+    #[test]
+    fn test_z_score_discounts_crowded_instances() {
+        let co_presence = |hours: u64, population: f64| {
+            CoPresence {
+                overlaps: 1,
+                seconds: hours * 3600,
+                ..CoPresence::default()
+            }
+            .in_crowd(population)
+        };
+        // a spent 10 hours with b in packed public worlds but only 3 with c in instances of three
+        let edges = HashMap::from([
+            ("b", co_presence(10, 41_f64)),
+            ("c", co_presence(3, 3_f64)),
+            ("d", co_presence(1, 41_f64)),
+            ("e", co_presence(1, 41_f64)),
+        ]);
+        let graph = HashMap::from([("a", edges)]);
+
+        let stats = &pair_stats(&graph, WeightMode::Time)["a"];
+        assert!(stats["b"].hours > stats["c"].hours);
+        assert!(stats["c"].z_score > stats["b"].z_score);
+        assert!(stats["b"].z_score > stats["d"].z_score);
+    }
+
+    #[test]
+    fn test_calibration_separates_the_samples() {
+        let samples = samples();
+        let mut classifiers: Vec<Box<dyn FriendshipClassifier>> = vec![
+            Box::<Thresholds>::default(),
+            Box::<ZScore>::default(),
+            Box::<Logistic>::default(),
+        ];
+        for classifier in &mut classifiers {
+            classifier.calibrate(&samples);
+            let confusion = Confusion::of(&samples, |it| classifier.is_friend(it));
+            assert!(
+                (confusion.f1() - 1_f64).abs() < f64::EPSILON,
+                "{confusion:?}"
+            );
+        }
+    }
+}
@@ -15,6 +15,7 @@ use std::sync::Arc;
 
 use sqlx::types::chrono::{DateTime, Utc};
 
+use crate::zaphkiel::error::ParseError;
 use crate::zaphkiel::join_leave_event::JoinLeaveEvent;
 use crate::zaphkiel::world_instance::WorldInstance;
 
@@ -67,25 +68,33 @@ impl GamelogJoinLeave {
     }
 }
 
-#[allow(clippy::fallible_impl_from)] // we want it to fail when it's wrong
-impl From<GamelogJoinLeaveRow> for GamelogJoinLeave {
+impl TryFrom<GamelogJoinLeaveRow> for GamelogJoinLeave {
+    type Error = ParseError;
+
     #[inline]
-    fn from(row: GamelogJoinLeaveRow) -> Self {
+    fn try_from(row: GamelogJoinLeaveRow) -> Result<Self, Self::Error> {
         let mut ret = Self::new();
         ret.id = row.id;
-        ret.created_at = row.created_at.parse().unwrap();
-        ret.event = row.r#type.parse().unwrap();
+        ret.created_at = row
+            .created_at
+            .parse()
+            .map_err(|_| ParseError::InvalidTimestamp(row.created_at))?;
+        ret.event = row.r#type.parse()?;
         ret.display_name = row.display_name.into();
-        ret.location = row.location.parse().ok();
+        ret.location = match row.location.as_str() {
+            // not in an instance, VRCX writes these instead of a location
+            "" | "offline" | "private" | "traveling" => None,
+            location => Some(location.parse()?),
+        };
         ret.user_id = match row.user_id {
             x if x.is_empty() => None,
             _ => Some(row.user_id.into()),
         };
         ret.time = match row.time {
             ..=0 => None,
-            _ => Some(row.time.try_into().unwrap()),
+            time => u64::try_from(time).ok(),
         };
 
-        ret
+        Ok(ret)
     }
 }
@@ -0,0 +1,383 @@
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::sync::Arc;
+
+use chrono::NaiveDate;
+use sqlx::types::chrono::{DateTime, Utc};
+
+use crate::zaphkiel::error::Result;
+use crate::zaphkiel::ids::Id;
+use crate::zaphkiel::metadata::Metadata;
+
+/// A node of an [`AttributedGraph`].
+///
+/// - `community`: index into `communities.ron`.
+/// - `degree`: number of people the user has an edge with, in either direction.
+/// - `first_seen`, `last_seen`: from the name history, `None` if the user isn't in it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeAttributes {
+    pub id: Id,
+    pub name: Arc<str>,
+    pub community: Option<usize>,
+    pub degree: usize,
+    pub first_seen: Option<DateTime<Utc>>,
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+/// A directed edge of an [`AttributedGraph`], `seconds` is the time the two users overlapped and
+/// `days` the days they did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgeAttributes {
+    pub source: Id,
+    pub target: Id,
+    pub seconds: u64,
+    pub metadata: Metadata,
+    pub days: BTreeSet<NaiveDate>,
+}
+
+/// The graph with everything GraphML and GEXF files carry, see [`write_graphml`] and
+/// [`write_gexf`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AttributedGraph {
+    pub nodes: Vec<NodeAttributes>,
+    pub edges: Vec<EdgeAttributes>,
+}
+
+/// (id, type) of the attributes of the edges, in the order they're written.
+const EDGE_ATTRIBUTES: [(&str, &str); 7] = [
+    ("count", "int"),
+    ("hours", "double"),
+    ("weight", "double"),
+    ("max", "double"),
+    ("total", "double"),
+    ("percentage", "double"),
+    ("percentile", "double"),
+];
+
+impl EdgeAttributes {
+    #[allow(clippy::cast_precision_loss)]
+    fn values(&self) -> [String; 7] {
+        let metadata = &self.metadata;
+        [
+            metadata.count.to_string(),
+            (self.seconds as f64 / 3600_f64).to_string(),
+            metadata.weight.to_string(),
+            metadata.max.to_string(),
+            metadata.total.to_string(),
+            metadata.percentage.to_string(),
+            metadata.percentile.to_string(),
+        ]
+    }
+}
+
+/// Write `graph` as [GraphML](http://graphml.graphdrawing.org/), for yEd and Gephi.
+///
+/// The display name is the `label` of a node, so Gephi picks it up, and the user id is the id of
+/// the node. Times are RFC 3339 strings, GraphML has no date type.
+///
+/// # Errors
+///
+/// Returns [`crate::zaphkiel::error::Error::Io`] if writing fails.
+#[inline]
+pub fn write_graphml(graph: &AttributedGraph, mut out: impl Write) -> Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://graphml.graphdrawing.org/xmlns http://graphml.graphdrawing.org/xmlns/1.0/graphml.xsd">"#
+    )?;
+    for (id, kind) in [
+        ("label", "string"),
+        ("community", "int"),
+        ("degree", "int"),
+        ("first_seen", "string"),
+        ("last_seen", "string"),
+    ] {
+        writeln!(
+            out,
+            r#"  <key id="{id}" for="node" attr.name="{id}" attr.type="{kind}"/>"#
+        )?;
+    }
+    for (id, kind) in EDGE_ATTRIBUTES {
+        writeln!(
+            out,
+            r#"  <key id="{id}" for="edge" attr.name="{id}" attr.type="{kind}"/>"#
+        )?;
+    }
+    writeln!(out, r#"  <graph id="co-presence" edgedefault="directed">"#)?;
+
+    for node in &graph.nodes {
+        writeln!(out, r#"    <node id="{}">"#, escape(node.id.as_str()))?;
+        writeln!(
+            out,
+            r#"      <data key="label">{}</data>"#,
+            escape(&node.name)
+        )?;
+        if let Some(community) = node.community {
+            writeln!(out, r#"      <data key="community">{community}</data>"#)?;
+        }
+        writeln!(out, r#"      <data key="degree">{}</data>"#, node.degree)?;
+        if let Some(first_seen) = node.first_seen {
+            writeln!(
+                out,
+                r#"      <data key="first_seen">{}</data>"#,
+                first_seen.to_rfc3339()
+            )?;
+        }
+        if let Some(last_seen) = node.last_seen {
+            writeln!(
+                out,
+                r#"      <data key="last_seen">{}</data>"#,
+                last_seen.to_rfc3339()
+            )?;
+        }
+        writeln!(out, "    </node>")?;
+    }
+
+    for (idx, edge) in graph.edges.iter().enumerate() {
+        writeln!(
+            out,
+            r#"    <edge id="e{idx}" source="{}" target="{}">"#,
+            escape(edge.source.as_str()),
+            escape(edge.target.as_str())
+        )?;
+        for ((key, _), value) in EDGE_ATTRIBUTES.iter().zip(edge.values()) {
+            writeln!(out, r#"      <data key="{key}">{value}</data>"#)?;
+        }
+        writeln!(out, "    </edge>")?;
+    }
+
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</graphml>")?;
+
+    Ok(())
+}
+
+/// Write `graph` as a dynamic [GEXF 1.3](https://gexf.net/) graph, for Gephi.
+///
+/// Nodes exist from the day they were first seen to the day they were last seen, and edges have a
+/// spell for every run of consecutive days the two users were seen together, so Gephi's timeline
+/// can slice the graph by time.
+///
+/// # Errors
+///
+/// Returns [`crate::zaphkiel::error::Error::Io`] if writing fails.
+#[inline]
+pub fn write_gexf(graph: &AttributedGraph, mut out: impl Write) -> Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<gexf xmlns="http://gexf.net/1.3" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://gexf.net/1.3 http://gexf.net/1.3/gexf.xsd" version="1.3">"#
+    )?;
+    writeln!(out, "  <meta>")?;
+    writeln!(out, "    <creator>vrcx-insights</creator>")?;
+    writeln!(out, "  </meta>")?;
+    writeln!(
+        out,
+        r#"  <graph defaultedgetype="directed" mode="dynamic" timeformat="date">"#
+    )?;
+
+    writeln!(out, r#"    <attributes class="node">"#)?;
+    for (id, kind) in [
+        ("community", "integer"),
+        ("degree", "integer"),
+        ("first_seen", "string"),
+        ("last_seen", "string"),
+    ] {
+        writeln!(
+            out,
+            r#"      <attribute id="{id}" title="{id}" type="{kind}"/>"#
+        )?;
+    }
+    writeln!(out, "    </attributes>")?;
+    writeln!(out, r#"    <attributes class="edge">"#)?;
+    for (id, kind) in EDGE_ATTRIBUTES {
+        let kind = if kind == "int" { "integer" } else { kind };
+        writeln!(
+            out,
+            r#"      <attribute id="{id}" title="{id}" type="{kind}"/>"#
+        )?;
+    }
+    writeln!(out, "    </attributes>")?;
+
+    writeln!(out, "    <nodes>")?;
+    for node in &graph.nodes {
+        let mut lifetime = String::new();
+        if let (Some(first_seen), Some(last_seen)) = (node.first_seen, node.last_seen) {
+            lifetime = format!(
+                r#" start="{}" end="{}""#,
+                first_seen.date_naive(),
+                last_seen.date_naive()
+            );
+        }
+        writeln!(
+            out,
+            r#"      <node id="{}" label="{}"{lifetime}>"#,
+            escape(node.id.as_str()),
+            escape(&node.name)
+        )?;
+        writeln!(out, "        <attvalues>")?;
+        if let Some(community) = node.community {
+            writeln!(
+                out,
+                r#"          <attvalue for="community" value="{community}"/>"#
+            )?;
+        }
+        writeln!(
+            out,
+            r#"          <attvalue for="degree" value="{}"/>"#,
+            node.degree
+        )?;
+        for (id, seen) in [
+            ("first_seen", node.first_seen),
+            ("last_seen", node.last_seen),
+        ] {
+            if let Some(seen) = seen {
+                writeln!(
+                    out,
+                    r#"          <attvalue for="{id}" value="{}"/>"#,
+                    seen.to_rfc3339()
+                )?;
+            }
+        }
+        writeln!(out, "        </attvalues>")?;
+        writeln!(out, "      </node>")?;
+    }
+    writeln!(out, "    </nodes>")?;
+
+    writeln!(out, "    <edges>")?;
+    for (idx, edge) in graph.edges.iter().enumerate() {
+        writeln!(
+            out,
+            r#"      <edge id="e{idx}" source="{}" target="{}" weight="{}">"#,
+            escape(edge.source.as_str()),
+            escape(edge.target.as_str()),
+            edge.metadata.weight
+        )?;
+        writeln!(out, "        <attvalues>")?;
+        for ((key, _), value) in EDGE_ATTRIBUTES.iter().zip(edge.values()) {
+            writeln!(out, r#"          <attvalue for="{key}" value="{value}"/>"#)?;
+        }
+        writeln!(out, "        </attvalues>")?;
+        let spells = spells(&edge.days);
+        if !spells.is_empty() {
+            writeln!(out, "        <spells>")?;
+            for (start, end) in spells {
+                writeln!(out, r#"          <spell start="{start}" end="{end}"/>"#)?;
+            }
+            writeln!(out, "        </spells>")?;
+        }
+        writeln!(out, "      </edge>")?;
+    }
+    writeln!(out, "    </edges>")?;
+
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</gexf>")?;
+
+    Ok(())
+}
+
+/// Runs of consecutive days, as inclusive (first, last) pairs.
+fn spells(days: &BTreeSet<NaiveDate>) -> Vec<(NaiveDate, NaiveDate)> {
+    let mut spells: Vec<(NaiveDate, NaiveDate)> = vec![];
+    for day in days {
+        match spells.last_mut() {
+            Some((_, end)) if end.succ_opt() == Some(*day) => *end = *day,
+            _ => spells.push((*day, *day)),
+        }
+    }
+    spells
+}
+
+/// Escape `value` for an attribute or text node. Control characters other than tab, LF and CR
+/// aren't allowed in XML 1.0 at all, not even as character references, so they are replaced with
+/// U+FFFD like an invalid byte would be.
+fn escape(value: &str) -> String {
+    let mut ret = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&apos;"),
+            '\t' | '\n' | '\r' => ret.push(c),
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => ret.push(char::REPLACEMENT_CHARACTER),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use chrono::NaiveDate;
+
+    use crate::zaphkiel::graph_xml::{
+        write_gexf, write_graphml, AttributedGraph, EdgeAttributes, NodeAttributes,
+    };
+    use crate::zaphkiel::metadata::Metadata;
+
+    #[test]
+    fn test_gexf_spells_and_escaping() {
+        let node = |id: &str, name: &str| NodeAttributes {
+            id: id.into(),
+            name: name.into(),
+            community: Some(0),
+            degree: 1,
+            first_seen: None,
+            last_seen: None,
+        };
+        let day = |day: u32| NaiveDate::from_ymd_opt(2023, 1, day).unwrap();
+        let graph = AttributedGraph {
+            nodes: vec![node("usr_a", "<A & B>"), node("usr_b", "B")],
+            edges: vec![EdgeAttributes {
+                source: "usr_a".into(),
+                target: "usr_b".into(),
+                seconds: 7200,
+                metadata: Metadata::new(3, 3_f64, 4_f64, 3_f64),
+                days: BTreeSet::from([day(1), day(2), day(3), day(7)]),
+            }],
+        };
+
+        let mut gexf = vec![];
+        write_gexf(&graph, &mut gexf).unwrap();
+        let gexf = String::from_utf8(gexf).unwrap();
+        assert!(gexf.contains(r#"label="&lt;A &amp; B&gt;""#));
+        assert!(gexf.contains(r#"<spell start="2023-01-01" end="2023-01-03"/>"#));
+        assert!(gexf.contains(r#"<spell start="2023-01-07" end="2023-01-07"/>"#));
+        assert!(gexf.contains(r#"<attvalue for="hours" value="2"/>"#));
+
+        let mut graphml = vec![];
+        write_graphml(&graph, &mut graphml).unwrap();
+        let graphml = String::from_utf8(graphml).unwrap();
+        assert!(graphml.contains(r#"<edge id="e0" source="usr_a" target="usr_b">"#));
+        assert!(graphml.contains(r#"<data key="count">3</data>"#));
+    }
+
+    #[test]
+    fn test_control_characters_are_replaced() {
+        let graph = AttributedGraph {
+            nodes: vec![NodeAttributes {
+                id: "usr_a".into(),
+                name: "A\u{0}\u{7}\tB\u{1b}[31m".into(),
+                community: None,
+                degree: 0,
+                first_seen: None,
+                last_seen: None,
+            }],
+            edges: vec![],
+        };
+
+        let mut gexf = vec![];
+        write_gexf(&graph, &mut gexf).unwrap();
+        let mut graphml = vec![];
+        write_graphml(&graph, &mut graphml).unwrap();
+        for xml in [gexf, graphml] {
+            let xml = String::from_utf8(xml).unwrap();
+            assert!(xml.contains("A\u{fffd}\u{fffd}\tB\u{fffd}[31m"), "{xml}");
+            assert!(!xml.contains(|c: char| c.is_control() && !matches!(c, '\t' | '\n' | '\r')));
+        }
+    }
+}
@@ -1,3 +1,7 @@
+use std::str::FromStr;
+
+use crate::zaphkiel::error::ParseError;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
 pub enum GroupAccessType {
     Public,
@@ -7,24 +11,35 @@ pub enum GroupAccessType {
     Other,
 }
 
-impl From<&str> for GroupAccessType {
+impl TryFrom<&str> for GroupAccessType {
+    type Error = ParseError;
+
     #[inline]
-    fn from(value: &str) -> Self {
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
         let value = value.to_lowercase();
-        Self::from(value)
+        match value.as_str() {
+            "public" => Ok(Self::Public),
+            "plus" => Ok(Self::Plus),
+            "members" => Ok(Self::Members),
+            _ => Err(ParseError::UnknownGroupAccessType(value)),
+        }
     }
 }
 
-#[allow(clippy::fallible_impl_from)] // we WANT it to fail if it's wrong
-impl From<String> for GroupAccessType {
+impl TryFrom<String> for GroupAccessType {
+    type Error = ParseError;
+
     #[inline]
-    fn from(value: String) -> Self {
-        let value = value.to_lowercase();
-        match value.as_str() {
-            "public" => Self::Public,
-            "plus" => Self::Plus,
-            "members" => Self::Members,
-            _ => panic!("Unknown group access type: {value}"),
-        }
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from(value.as_str())
+    }
+}
+
+impl FromStr for GroupAccessType {
+    type Err = ParseError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from(s)
     }
 }
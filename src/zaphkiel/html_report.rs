@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::zaphkiel::community::Communities;
+use crate::zaphkiel::error::Result;
+use crate::zaphkiel::graph_xml::AttributedGraph;
+use crate::zaphkiel::ids::Id;
+
+/// The page, `/*REPORT*/` is replaced by the data of the report.
+const TEMPLATE: &str = include_str!("html_report.html");
+
+/// A person of the report, `top` are the people they spent the most time with, heaviest first.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReportPerson {
+    pub id: Id,
+    pub name: Arc<str>,
+    pub community: Option<usize>,
+    pub degree: usize,
+    pub top: Vec<ReportLink>,
+}
+
+/// An edge from the point of view of one person, `other` is an index into
+/// [`HtmlReport::people`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ReportLink {
+    pub other: usize,
+    pub count: u32,
+    pub hours: f64,
+    pub weight: f64,
+    pub percentage: f64,
+    pub percentile: f64,
+}
+
+/// A friend circle, `members` are indices into [`HtmlReport::people`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReportCommunity {
+    pub name: String,
+    pub members: Vec<usize>,
+    pub cohesion: f64,
+    pub density: f64,
+}
+
+/// Everything shown by the HTML report, see [`write_html_report`].
+///
+/// `edges` are undirected `(a, b, weight)` triples for the graph drawing, with the heavier of the
+/// two directions.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HtmlReport {
+    pub title: String,
+    pub weight: String,
+    pub people: Vec<ReportPerson>,
+    pub edges: Vec<(usize, usize, f64)>,
+    pub communities: Vec<ReportCommunity>,
+}
+
+impl HtmlReport {
+    /// Build the report from the same graph as the GraphML and GEXF files, keeping the `top`
+    /// heaviest edges of everyone for their card.
+    #[must_use]
+    #[inline]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn new(
+        title: String,
+        weight: String,
+        graph: &AttributedGraph,
+        communities: &Communities<Id>,
+        top: usize,
+    ) -> Self {
+        let index = graph
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(idx, node)| (&node.id, idx))
+            .collect::<HashMap<_, _>>();
+
+        let mut people = graph
+            .nodes
+            .iter()
+            .map(|node| ReportPerson {
+                id: node.id.clone(),
+                name: node.name.clone(),
+                community: node.community,
+                degree: node.degree,
+                top: vec![],
+            })
+            .collect::<Vec<_>>();
+
+        let mut edges: HashMap<(usize, usize), f64> = HashMap::new();
+        for edge in &graph.edges {
+            let (Some(&source), Some(&target)) = (index.get(&edge.source), index.get(&edge.target))
+            else {
+                continue;
+            };
+            let metadata = &edge.metadata;
+            people[source].top.push(ReportLink {
+                other: target,
+                count: metadata.count,
+                hours: edge.seconds as f64 / 3600_f64,
+                weight: metadata.weight,
+                percentage: metadata.percentage,
+                percentile: metadata.percentile,
+            });
+            let weight = edges
+                .entry((source.min(target), source.max(target)))
+                .or_default();
+            *weight = weight.max(metadata.weight);
+        }
+        for person in &mut people {
+            person.top.sort_by(|a, b| {
+                b.weight
+                    .total_cmp(&a.weight)
+                    .then_with(|| a.other.cmp(&b.other))
+            });
+            person.top.truncate(top);
+        }
+
+        let mut edges = edges
+            .into_iter()
+            .map(|((a, b), weight)| (a, b, weight))
+            .collect::<Vec<_>>();
+        edges.sort_by_key(|it| (it.0, it.1));
+
+        let communities = communities
+            .communities
+            .iter()
+            .map(|community| ReportCommunity {
+                name: community.name.clone(),
+                members: community
+                    .members
+                    .iter()
+                    .filter_map(|member| index.get(member).copied())
+                    .collect(),
+                cohesion: community.cohesion,
+                density: community.density,
+            })
+            .collect();
+
+        Self {
+            title,
+            weight,
+            people,
+            edges,
+            communities,
+        }
+    }
+}
+
+/// Write `report` as a single HTML page that works offline, the data and the scripts are inline.
+///
+/// # Errors
+///
+/// Returns [`crate::zaphkiel::error::Error::Io`] if writing fails.
+#[inline]
+pub fn write_html_report(report: &HtmlReport, mut out: impl Write) -> Result<()> {
+    // `</script>` in a display name would end the script early
+    let data = serde_json::to_string(report)?.replace("</", "<\\/");
+    let page = TEMPLATE
+        .replace("/*TITLE*/", &escape(&report.title))
+        .replace("/*REPORT*/", &data);
+    out.write_all(page.as_bytes())?;
+
+    Ok(())
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::zaphkiel::community::{Communities, Community};
+    use crate::zaphkiel::graph_xml::{AttributedGraph, EdgeAttributes, NodeAttributes};
+    use crate::zaphkiel::html_report::{write_html_report, HtmlReport};
+    use crate::zaphkiel::metadata::Metadata;
+
+    #[test]
+    fn test_report_is_inline_and_escaped() {
+        let node = |id: &str, name: &str| NodeAttributes {
+            id: id.into(),
+            name: name.into(),
+            community: Some(0),
+            degree: 1,
+            first_seen: None,
+            last_seen: None,
+        };
+        let edge = |source: &str, target: &str, weight: f64| EdgeAttributes {
+            source: source.into(),
+            target: target.into(),
+            seconds: 3600,
+            metadata: Metadata::new(1, weight, weight + 1_f64, weight),
+            days: BTreeSet::new(),
+        };
+        let graph = AttributedGraph {
+            nodes: vec![node("usr_a", "</script>"), node("usr_b", "B")],
+            edges: vec![edge("usr_a", "usr_b", 2_f64), edge("usr_b", "usr_a", 3_f64)],
+        };
+        let communities = Communities {
+            resolution: 1_f64,
+            modularity: 0_f64,
+            communities: vec![Community {
+                name: "B's circle".to_string(),
+                members: vec!["usr_b".into(), "usr_a".into()],
+                internal_weight: 3_f64,
+                cohesion: 1_f64,
+                density: 1_f64,
+            }],
+        };
+
+        let report = HtmlReport::new("test".into(), "count".into(), &graph, &communities, 10);
+        assert_eq!(report.edges, vec![(0, 1, 3_f64)]);
+        assert_eq!(report.communities[0].members, vec![1, 0]);
+
+        let mut html = vec![];
+        write_html_report(&report, &mut html).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(!html.contains("/*REPORT*/"));
+        assert!(!html.contains("\"</script>\""));
+        assert!(!html.contains("http://") && !html.contains("https://"));
+    }
+}
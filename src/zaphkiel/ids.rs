@@ -1,26 +1,21 @@
-use std::sync::{Arc, LazyLock, OnceLock};
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
-pub static KAT_ID: LazyLock<Id> =
-    LazyLock::new(|| Id("usr_c2a23c47-1622-4b7a-90a4-b824fcaacc69".into()));
-pub static KAT_DISPLAY_NAME: OnceLock<Name> = OnceLock::new();
-pub static KAT_EXISTS: LazyLock<bool> = LazyLock::new(|| std::fs::metadata(".kat").is_ok());
-
-pub trait IsKat {
-    fn is_kat(&self) -> bool;
-}
+/// A VRChat user id, `usr_...`.
+#[derive(Debug, Eq, PartialEq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Id(Arc<str>);
 
-impl IsKat for Arc<str> {
+impl Id {
+    #[must_use]
     #[inline]
-    fn is_kat(&self) -> bool {
-        self.clone() == KAT_ID.0 || self.clone() == KAT_DISPLAY_NAME.get().unwrap().0
+    pub fn as_str(&self) -> &str {
+        &self.0
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Hash)]
-pub struct Id(Arc<str>);
-
 impl Clone for Id {
     #[inline]
     fn clone(&self) -> Self {
@@ -28,10 +23,10 @@ impl Clone for Id {
     }
 }
 
-impl ToString for Id {
+impl Display for Id {
     #[inline]
-    fn to_string(&self) -> String {
-        self.0.to_string()
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
     }
 }
 
@@ -70,14 +65,9 @@ impl From<Id> for Arc<str> {
     }
 }
 
-impl IsKat for Id {
-    #[inline]
-    fn is_kat(&self) -> bool {
-        *self == *KAT_ID
-    }
-}
-
-#[derive(Debug, Eq, PartialEq, Hash, Deserialize, Serialize)]
+/// A display name, users can change theirs so it doesn't identify them.
+#[derive(Debug, Eq, PartialEq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(transparent)]
 pub struct Name(pub Arc<str>);
 
 impl Clone for Name {
@@ -87,10 +77,10 @@ impl Clone for Name {
     }
 }
 
-impl ToString for Name {
+impl Display for Name {
     #[inline]
-    fn to_string(&self) -> String {
-        self.0.to_string()
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
     }
 }
 
@@ -118,7 +108,7 @@ impl From<Name> for Arc<str> {
 impl From<&Name> for Arc<str> {
     #[inline]
     fn from(value: &Name) -> Self {
-        value.into()
+        value.0.clone()
     }
 }
 
@@ -128,10 +118,3 @@ impl AsRef<str> for Name {
         self.0.as_ref()
     }
 }
-
-impl IsKat for Name {
-    #[inline]
-    fn is_kat(&self) -> bool {
-        self == KAT_DISPLAY_NAME.get().unwrap()
-    }
-}
@@ -1,5 +1,7 @@
 use std::str::FromStr;
 
+use crate::zaphkiel::error::ParseError;
+
 #[derive(Debug, Clone, Copy, sqlx::Type, Default)]
 pub enum JoinLeaveEvent {
     Join,
@@ -8,34 +10,36 @@ pub enum JoinLeaveEvent {
     Other,
 }
 
-impl From<&str> for JoinLeaveEvent {
-    #[inline]
-    fn from(value: &str) -> Self {
-        let value = value.to_lowercase();
-        Self::from(value)
-    }
-}
+impl TryFrom<&str> for JoinLeaveEvent {
+    type Error = ParseError;
 
-#[allow(clippy::fallible_impl_from)] // we want it to fail if it's wrong
-impl From<String> for JoinLeaveEvent {
     #[inline]
-    fn from(value: String) -> Self {
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
         let value = value.to_lowercase();
         match value.as_str() {
-            "join" | "joins" | "joined" | "onplayerjoined" => Self::Join,
+            "join" | "joins" | "joined" | "onplayerjoined" => Ok(Self::Join),
 
-            "leave" | "leaves" | "left" | "onplayerleft" => Self::Leave,
+            "leave" | "leaves" | "left" | "onplayerleft" => Ok(Self::Leave),
 
-            _ => panic!("Unknown join/leave event: {value}"),
+            _ => Err(ParseError::UnknownJoinLeaveEvent(value)),
         }
     }
 }
 
+impl TryFrom<String> for JoinLeaveEvent {
+    type Error = ParseError;
+
+    #[inline]
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from(value.as_str())
+    }
+}
+
 impl FromStr for JoinLeaveEvent {
-    type Err = std::string::ParseError;
+    type Err = ParseError;
 
     #[inline]
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self::from(s))
+        Self::try_from(s)
     }
 }
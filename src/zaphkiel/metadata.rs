@@ -1,31 +1,107 @@
 use std::cmp::Ordering;
 use std::fmt::Formatter;
 
-use serde::de::{SeqAccess, Visitor};
+use serde::de::{self, SeqAccess, Visitor};
 use serde::ser::SerializeTuple;
 use serde::{Deserializer, Serializer};
 
+use crate::zaphkiel::co_presence::CoPresence;
+use crate::zaphkiel::weight::WeightMode;
+
+/// An edge of `graph2_sorted`, relative to all the edges of its node.
+///
+/// - `count`: number of overlapping stays.
+/// - `weight`: weight of the edge, see [`crate::zaphkiel::weight::WeightMode`].
+/// - `max`: highest weight of the node, + 1 with [`WeightMode::Count`] so a sighting count never
+///   reaches 100%.
+/// - `total`: sum of the weights of the node.
+/// - `percentage`: `weight / total * 100`, rounded to 2 decimal places, `0` when `total` is.
+/// - `percentile`: `weight / max * 100`, rounded to 2 decimal places, `0` when `max` is.
 #[derive(Debug, Clone, Copy)]
 pub struct Metadata {
     pub count: u32,
-    pub max: u32,
-    pub total: u32,
+    pub weight: f64,
+    pub max: f64,
+    pub total: f64,
     pub percentage: f64,
     pub percentile: f64,
 }
 
+impl Metadata {
+    #[must_use]
+    #[inline]
+    pub fn new(count: u32, weight: f64, max: f64, total: f64) -> Self {
+        // a node whose overlaps all lasted under a second weighs nothing in `Time` mode
+        let share = |of: f64| {
+            if of > 0_f64 {
+                ((weight * 10_000_f64) / of).round() / 100_f64
+            } else {
+                0_f64
+            }
+        };
+        let percentage = share(total);
+        let percentile = share(max);
+
+        Self {
+            count,
+            weight,
+            max,
+            total,
+            percentage,
+            percentile,
+        }
+    }
+}
+
+/// [`Metadata`] of every edge of a node, weighted by `mode`.
+#[must_use]
+#[inline]
+pub fn metadata_of<'a, N: 'a>(
+    edges: impl IntoIterator<Item = (&'a N, &'a CoPresence)>,
+    mode: WeightMode,
+) -> Vec<(&'a N, Metadata)> {
+    let weights = edges
+        .into_iter()
+        .map(|(other, co_presence)| (other, co_presence.overlaps, mode.weight(co_presence)))
+        .collect::<Vec<_>>();
+    let total = weights.iter().map(|(_, _, weight)| weight).sum::<f64>();
+    let Some(max) = weights
+        .iter()
+        .map(|(_, _, weight)| *weight)
+        .max_by(f64::total_cmp)
+    else {
+        return vec![];
+    };
+
+    // the + 1 only makes sense for whole sighting counts, hours or logs would depend on the unit
+    let max = if mode == WeightMode::Count {
+        max + 1_f64
+    } else {
+        max
+    };
+
+    weights
+        .into_iter()
+        .map(|(other, count, weight)| (other, Metadata::new(count, weight, max, total)))
+        .collect()
+}
+
 impl PartialEq for Metadata {
     #[inline]
     fn eq(&self, other: &Self) -> bool {
         let &Self {
             count,
+            weight,
             total,
             max,
             percentage: _,
             percentile: _,
         } = self;
 
-        count == other.count && total == other.total && max == other.max
+        count == other.count
+            && weight.total_cmp(&other.weight).is_eq()
+            && total.total_cmp(&other.total).is_eq()
+            && max.total_cmp(&other.max).is_eq()
     }
 }
 
@@ -37,14 +113,16 @@ impl serde::Serialize for Metadata {
     {
         let Self {
             count,
+            weight,
             max,
             total,
             percentage,
             percentile,
         } = self;
 
-        let mut s = serializer.serialize_tuple(5)?;
+        let mut s = serializer.serialize_tuple(6)?;
         s.serialize_element(count)?;
+        s.serialize_element(weight)?;
         s.serialize_element(max)?;
         s.serialize_element(total)?;
         s.serialize_element(percentage)?;
@@ -60,24 +138,42 @@ impl<'de> Visitor<'de> for MetadataVisitor {
 
     #[inline]
     fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
-        formatter.write_str("a tuple of len 5")
+        formatter.write_str("a tuple of len 6")
     }
 
     #[inline]
-    fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
     where
         A: SeqAccess<'de>,
     {
-        // $(
-        //         let $name = match tri!(seq.next_element()) {
-        //         Some(value) => value,
-        //         None => return Err(Error::invalid_length($n, &self)),
-        //     };
-        // )+
-        //
-        // Ok(($($name,)+))
-
-        todo!()
+        let count = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let weight = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let max = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        let total = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(3, &self))?;
+        let percentage = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(4, &self))?;
+        let percentile = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(5, &self))?;
+
+        // kept as written rather than recomputed, so a file reads back exactly as it was
+        Ok(Metadata {
+            count,
+            weight,
+            max,
+            total,
+            percentage,
+            percentile,
+        })
     }
 }
 
@@ -87,7 +183,7 @@ impl<'de> serde::Deserialize<'de> for Metadata {
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_tuple(5, MetadataVisitor)
+        deserializer.deserialize_tuple(6, MetadataVisitor)
     }
 }
 
@@ -99,17 +195,20 @@ impl PartialOrd for Metadata {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         let &Self {
             count,
+            weight,
             total,
             max,
             percentage: _,
             percentile: _,
         } = self;
-        let count = count.cmp(&other.count);
-        let total = total.cmp(&other.total);
-        let max = max.cmp(&other.max);
+        let weight = weight
+            .total_cmp(&other.weight)
+            .then_with(|| count.cmp(&other.count));
+        let total = total.total_cmp(&other.total);
+        let max = max.total_cmp(&other.max);
 
         match (total, max) {
-            (Ordering::Equal, Ordering::Equal) => Some(count),
+            (Ordering::Equal, Ordering::Equal) => Some(weight),
             _ => None,
         }
     }
@@ -118,6 +217,54 @@ impl PartialOrd for Metadata {
 impl Ord for Metadata {
     #[inline]
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        self.partial_cmp(other).unwrap_or_else(|| {
+            self.total
+                .total_cmp(&other.total)
+                .then_with(|| self.max.total_cmp(&other.max))
+                .then_with(|| self.weight.total_cmp(&other.weight))
+                .then_with(|| self.count.cmp(&other.count))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::zaphkiel::co_presence::CoPresence;
+    use crate::zaphkiel::metadata::metadata_of;
+    use crate::zaphkiel::weight::WeightMode;
+
+    #[test]
+    fn test_percentile_of_fractional_weights() {
+        // half an hour with a, a quarter of an hour with b
+        let a = CoPresence {
+            overlaps: 1,
+            seconds: 1800,
+            ..CoPresence::default()
+        };
+        let b = CoPresence {
+            overlaps: 1,
+            seconds: 900,
+            ..CoPresence::default()
+        };
+        let edges = [("a", a), ("b", b)];
+
+        let metadata = metadata_of(edges.iter().map(|(k, v)| (k, v)), WeightMode::Time);
+        assert!((metadata[0].1.percentile - 100_f64).abs() < 1e-9);
+        assert!((metadata[1].1.percentile - 50_f64).abs() < 1e-9);
+
+        // counts keep the + 1
+        let metadata = metadata_of(edges.iter().map(|(k, v)| (k, v)), WeightMode::Count);
+        assert!((metadata[0].1.percentile - 50_f64).abs() < 1e-9);
+
+        // overlaps under a second weigh nothing, which mustn't turn into NaN
+        let instant = CoPresence {
+            overlaps: 1,
+            ..CoPresence::default()
+        };
+        for mode in [WeightMode::Time, WeightMode::LogTime] {
+            let metadata = metadata_of([(&"c", &instant)], mode);
+            assert_eq!(metadata[0].1.percentage, 0_f64);
+            assert_eq!(metadata[0].1.percentile, 0_f64);
+        }
     }
 }
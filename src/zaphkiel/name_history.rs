@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+
+use crate::zaphkiel::error::ParseError;
+use crate::zaphkiel::ids::Id;
+
+/// One display name of a user, aggregated over `gamelog_join_leave`.
+#[derive(Debug, sqlx::FromRow, Clone)]
+#[allow(clippy::module_name_repetitions)]
+pub struct NameHistoryRow {
+    pub user_id: String,
+    pub display_name: String,
+    pub first_seen: String,
+    pub last_seen: String,
+    pub sightings: i64,
+}
+
+/// A display name and when it was used, `sightings` is the number of join and leave events
+/// logged under it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameUse {
+    pub name: Arc<str>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub sightings: u32,
+}
+
+/// Every display name a user has used, oldest first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(clippy::module_name_repetitions)]
+pub struct NameHistory {
+    pub user_id: Id,
+    pub names: Vec<NameUse>,
+}
+
+impl NameHistory {
+    /// The name the user was last seen with.
+    #[must_use]
+    #[inline]
+    pub fn current(&self) -> Option<&Arc<str>> {
+        self.names
+            .iter()
+            .max_by_key(|it| it.last_seen)
+            .map(|it| &it.name)
+    }
+
+    /// Whether the user has used `name`, ignoring case like VRChat does.
+    #[must_use]
+    #[inline]
+    pub fn has_used(&self, name: &str) -> bool {
+        self.names
+            .iter()
+            .any(|it| it.name.to_lowercase() == name.to_lowercase())
+    }
+}
+
+/// Group rows by user, histories are sorted by id.
+///
+/// # Errors
+///
+/// Returns [`ParseError::InvalidTimestamp`] if a row has an invalid timestamp.
+#[inline]
+pub fn histories_of(rows: Vec<NameHistoryRow>) -> Result<Vec<NameHistory>, ParseError> {
+    let mut users: HashMap<Id, Vec<NameUse>> = HashMap::new();
+    for row in rows {
+        let parse = |timestamp: String| {
+            timestamp
+                .parse()
+                .map_err(|_| ParseError::InvalidTimestamp(timestamp))
+        };
+        users.entry(row.user_id.into()).or_default().push(NameUse {
+            name: row.display_name.into(),
+            first_seen: parse(row.first_seen)?,
+            last_seen: parse(row.last_seen)?,
+            sightings: u32::try_from(row.sightings).unwrap_or_default(),
+        });
+    }
+
+    let mut histories = users
+        .into_iter()
+        .map(|(user_id, mut names)| {
+            names.sort_by(|a, b| (a.first_seen, &a.name).cmp(&(b.first_seen, &b.name)));
+            NameHistory { user_id, names }
+        })
+        .collect::<Vec<_>>();
+    histories.sort_by(|a, b| a.user_id.cmp(&b.user_id));
+
+    Ok(histories)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::zaphkiel::name_history::{histories_of, NameHistoryRow};
+
+    #[test]
+    fn test_renamed_user() {
+        let row = |name: &str, first_seen: &str, last_seen: &str| NameHistoryRow {
+            user_id: "usr_a".to_string(),
+            display_name: name.to_string(),
+            first_seen: first_seen.to_string(),
+            last_seen: last_seen.to_string(),
+            sightings: 2,
+        };
+        let histories = histories_of(vec![
+            row(
+                "New",
+                "2023-02-01T00:00:00.000Z",
+                "2023-03-01T00:00:00.000Z",
+            ),
+            row(
+                "Old",
+                "2023-01-01T00:00:00.000Z",
+                "2023-01-31T00:00:00.000Z",
+            ),
+        ])
+        .unwrap();
+
+        assert_eq!(histories.len(), 1);
+        let history = &histories[0];
+        assert_eq!(&*history.names[0].name, "Old");
+        assert_eq!(history.current().map(|it| &**it), Some("New"));
+        assert!(history.has_used("old"));
+    }
+}
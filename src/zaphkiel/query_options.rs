@@ -0,0 +1,198 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::zaphkiel::error::{Error, Result};
+use crate::zaphkiel::exclusion::{ExclusionList, ExclusionReport};
+use crate::zaphkiel::gamelog_join_leave::{GamelogJoinLeave, GamelogJoinLeaveRow};
+use crate::zaphkiel::session::{sessions_of, Session};
+use crate::zaphkiel::time_filter::TimeFilter;
+
+/// What to do with a `gamelog_join_leave` row that can't be parsed.
+///
+/// - `Abort`: stop and return [`Error::MalformedRow`].
+/// - `Skip`: leave the row out and count it, see [`QueryOptions::skipped`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnMalformed {
+    #[default]
+    Abort,
+    Skip,
+}
+
+/// Options shared by every query of the library.
+///
+/// Cloning is cheap and clones share the same counters, so the options can be handed to spawned
+/// tasks and read back once they're done.
+#[derive(Debug, Clone, Default)]
+#[allow(clippy::module_name_repetitions)]
+pub struct QueryOptions {
+    pub on_malformed: OnMalformed,
+    pub exclusions: Arc<ExclusionList>,
+    pub time: Arc<TimeFilter>,
+    skipped: Arc<AtomicU64>,
+    excluded: Arc<Mutex<ExclusionReport>>,
+}
+
+impl QueryOptions {
+    #[must_use]
+    #[inline]
+    pub fn new(on_malformed: OnMalformed) -> Self {
+        Self {
+            on_malformed,
+            ..Self::default()
+        }
+    }
+
+    /// Leave out the rows matched by `exclusions` in every query.
+    #[must_use]
+    #[inline]
+    pub fn with_exclusions(self, exclusions: ExclusionList) -> Self {
+        Self {
+            exclusions: Arc::new(exclusions),
+            ..self
+        }
+    }
+
+    /// Only keep the times `time` keeps in every query.
+    #[must_use]
+    #[inline]
+    pub fn with_time_filter(self, time: TimeFilter) -> Self {
+        Self {
+            time: Arc::new(time),
+            ..self
+        }
+    }
+
+    /// Number of malformed rows skipped so far.
+    #[must_use]
+    #[inline]
+    pub fn skipped(&self) -> u64 {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    /// Who and what has been left out by [`QueryOptions::exclusions`] so far.
+    #[must_use]
+    #[inline]
+    pub fn excluded(&self) -> ExclusionReport {
+        self.excluded
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Parse rows according to [`QueryOptions::on_malformed`] and leave out the ones matched by
+    /// [`QueryOptions::exclusions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MalformedRow`] for the first row that can't be parsed when
+    /// `on_malformed` is [`OnMalformed::Abort`].
+    #[inline]
+    pub fn parse_rows(&self, rows: Vec<GamelogJoinLeaveRow>) -> Result<Vec<GamelogJoinLeave>> {
+        let mut ret = Vec::with_capacity(rows.len());
+        let mut excluded = ExclusionReport::default();
+        for row in rows {
+            let id = row.id;
+            match GamelogJoinLeave::try_from(row) {
+                Ok(row) if self.exclusions.is_empty() => ret.push(row),
+                Ok(row) => {
+                    if !excluded.record(&self.exclusions, &row) {
+                        ret.push(row);
+                    }
+                }
+                Err(_) if self.on_malformed == OnMalformed::Skip => {
+                    self.skipped.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(source) => return Err(Error::MalformedRow { id, source }),
+            }
+        }
+        if excluded.rows > 0 {
+            self.excluded
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .merge(excluded);
+        }
+
+        Ok(ret)
+    }
+
+    /// Parse rows with [`QueryOptions::parse_rows`], replay them into sessions and cut the
+    /// sessions down to what [`QueryOptions::time`] keeps.
+    ///
+    /// # Errors
+    ///
+    /// See [`QueryOptions::parse_rows`].
+    #[inline]
+    pub fn sessions_of(&self, rows: Vec<GamelogJoinLeaveRow>) -> Result<Vec<Session>> {
+        let sessions = sessions_of(&self.parse_rows(rows)?);
+        if self.time.is_empty() {
+            return Ok(sessions);
+        }
+
+        Ok(sessions
+            .iter()
+            .flat_map(|session| self.time.clip(session))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::zaphkiel::error::{Error, ParseError};
+    use crate::zaphkiel::gamelog_join_leave::GamelogJoinLeaveRow;
+    use crate::zaphkiel::query_options::{OnMalformed, QueryOptions};
+
+    fn rows() -> Vec<GamelogJoinLeaveRow> {
+        let row = GamelogJoinLeaveRow {
+            id: 1,
+            created_at: "2023-01-01T00:00:00.000Z".to_string(),
+            r#type: "OnPlayerJoined".to_string(),
+            display_name: "A".to_string(),
+            location: "world_id:instance_id~region(eu)".to_string(),
+            user_id: "usr_a".to_string(),
+            time: 0,
+        };
+        let bad = GamelogJoinLeaveRow {
+            id: 2,
+            r#type: "OnPlayerExploded".to_string(),
+            ..row.clone()
+        };
+        vec![row, bad]
+    }
+
+    #[test]
+    fn test_abort_on_malformed_row() {
+        let options = QueryOptions::new(OnMalformed::Abort);
+        let err = options.parse_rows(rows()).unwrap_err();
+        assert!(matches!(err, Error::MalformedRow { id: 2, .. }));
+    }
+
+    #[test]
+    fn test_skip_malformed_row() {
+        let options = QueryOptions::new(OnMalformed::Skip);
+        let parsed = options.clone().parse_rows(rows()).unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(options.skipped(), 1);
+    }
+
+    #[test]
+    fn test_unknown_region_is_malformed() {
+        let mut rows = rows();
+        rows.truncate(1);
+        rows[0].location = "world_id:instance_id~region(moon)".to_string();
+
+        let err = QueryOptions::new(OnMalformed::Abort)
+            .parse_rows(rows.clone())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::MalformedRow {
+                id: 1,
+                source: ParseError::WorldInstance(_)
+            }
+        ));
+
+        let options = QueryOptions::new(OnMalformed::Skip);
+        assert!(options.clone().parse_rows(rows).unwrap().is_empty());
+        assert_eq!(options.skipped(), 1);
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::zaphkiel::ids::Id;
+use crate::zaphkiel::metadata::Metadata;
+
+/// How much two people matter to each other, `a` is the one giving the most attention.
+///
+/// - `weight`: weight of the edge, the same in both directions.
+/// - `a_to_b`: percentage of `a`'s weight that goes to `b`.
+/// - `b_to_a`: percentage of `b`'s weight that goes to `a`, never more than `a_to_b`.
+/// - `reciprocity`: `b_to_a / a_to_b`, `1` when they matter as much to each other and close to
+///   `0` when only `a` cares.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PairReciprocity {
+    pub a: Id,
+    pub b: Id,
+    pub weight: f64,
+    pub a_to_b: f64,
+    pub b_to_a: f64,
+    pub reciprocity: f64,
+}
+
+/// The attention someone gives and gets over all their pairs.
+///
+/// - `pairs`: number of pairs it's averaged over.
+/// - `given`: mean percentage of their weight that goes to each of the others.
+/// - `received`: mean percentage of the others' weight that goes to them.
+/// - `balance`: `received - given`, positive for people who matter more to others than the
+///   others matter to them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attention {
+    pub user: Id,
+    pub pairs: usize,
+    pub given: f64,
+    pub received: f64,
+    pub balance: f64,
+}
+
+/// The asymmetry of `graph2_sorted`, see [`Reciprocity::of`].
+///
+/// - `pairs`: every pair, the most one-sided first.
+/// - `attention`: everyone in a pair, highest `balance` first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reciprocity {
+    pub pairs: Vec<PairReciprocity>,
+    pub attention: Vec<Attention>,
+}
+
+impl Reciprocity {
+    /// Compare both directions of every edge of `graph2_sorted`, where percentages are relative
+    /// to each user's own total.
+    ///
+    /// Pairs with only one direction, like the people at the edge of an ego graph whose own
+    /// edges weren't collected, are left out.
+    #[must_use]
+    #[inline]
+    pub fn of(graph2_sorted: &BTreeMap<Id, Vec<(Id, Metadata)>>) -> Self {
+        let edges = graph2_sorted
+            .iter()
+            .flat_map(|(user_id, others)| {
+                others
+                    .iter()
+                    .map(move |(other, metadata)| ((user_id, other), metadata))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut pairs = edges
+            .iter()
+            .filter(|((user_id, other), _)| user_id < other)
+            .filter_map(|((user_id, other), metadata)| {
+                let back = edges.get(&(*other, *user_id))?;
+                let (a, b, a_to_b, b_to_a) = if metadata.percentage >= back.percentage {
+                    (user_id, other, metadata.percentage, back.percentage)
+                } else {
+                    (other, user_id, back.percentage, metadata.percentage)
+                };
+                Some(PairReciprocity {
+                    a: (*a).clone(),
+                    b: (*b).clone(),
+                    weight: metadata.weight.max(back.weight),
+                    a_to_b,
+                    b_to_a,
+                    reciprocity: if a_to_b > 0_f64 {
+                        b_to_a / a_to_b
+                    } else {
+                        1_f64
+                    },
+                })
+            })
+            .collect::<Vec<_>>();
+        pairs.sort_by(|x, y| {
+            (y.a_to_b - y.b_to_a)
+                .total_cmp(&(x.a_to_b - x.b_to_a))
+                .then_with(|| x.a.cmp(&y.a))
+                .then_with(|| x.b.cmp(&y.b))
+        });
+
+        // (pairs, sum of given, sum of received)
+        let mut sums: BTreeMap<&Id, (usize, f64, f64)> = BTreeMap::new();
+        for pair in &pairs {
+            for (user_id, given, received) in [
+                (&pair.a, pair.a_to_b, pair.b_to_a),
+                (&pair.b, pair.b_to_a, pair.a_to_b),
+            ] {
+                let sum = sums.entry(user_id).or_default();
+                sum.0 += 1;
+                sum.1 += given;
+                sum.2 += received;
+            }
+        }
+        let mut attention = sums
+            .into_iter()
+            .map(|(user_id, (count, given, received))| {
+                #[allow(clippy::cast_precision_loss)]
+                let count_f64 = count as f64;
+                Attention {
+                    user: user_id.clone(),
+                    pairs: count,
+                    given: given / count_f64,
+                    received: received / count_f64,
+                    balance: (received - given) / count_f64,
+                }
+            })
+            .collect::<Vec<_>>();
+        attention.sort_by(|x, y| {
+            y.balance
+                .total_cmp(&x.balance)
+                .then_with(|| x.user.cmp(&y.user))
+        });
+
+        Self { pairs, attention }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::zaphkiel::ids::Id;
+    use crate::zaphkiel::metadata::Metadata;
+    use crate::zaphkiel::reciprocity::Reciprocity;
+
+    #[test]
+    fn test_one_sided_pair() {
+        let (a, b, c) = (Id::from("a"), Id::from("b"), Id::from("c"));
+        // a is most of b's time, but b is only a small part of a's
+        let graph = BTreeMap::from([
+            (
+                a.clone(),
+                vec![
+                    (c.clone(), Metadata::new(90, 90_f64, 91_f64, 100_f64)),
+                    (b.clone(), Metadata::new(10, 10_f64, 91_f64, 100_f64)),
+                ],
+            ),
+            (
+                b.clone(),
+                vec![(a.clone(), Metadata::new(10, 10_f64, 11_f64, 10_f64))],
+            ),
+            (
+                c.clone(),
+                vec![(a.clone(), Metadata::new(90, 90_f64, 91_f64, 90_f64))],
+            ),
+        ]);
+
+        let reciprocity = Reciprocity::of(&graph);
+        assert_eq!(reciprocity.pairs.len(), 2);
+        let pair = &reciprocity.pairs[0];
+        assert_eq!((&pair.a, &pair.b), (&b, &a));
+        assert!((pair.a_to_b - 100_f64).abs() < 1e-9);
+        assert!((pair.b_to_a - 10_f64).abs() < 1e-9);
+        assert!((pair.reciprocity - 0.1).abs() < 1e-9);
+
+        // everyone else spends all their time with a
+        assert_eq!(reciprocity.attention[0].user, a);
+        assert_eq!(reciprocity.attention[0].pairs, 2);
+        assert!((reciprocity.attention[0].received - 100_f64).abs() < 1e-9);
+        assert!((reciprocity.attention[0].given - 50_f64).abs() < 1e-9);
+    }
+}
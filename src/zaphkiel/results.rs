@@ -0,0 +1,342 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::zaphkiel::centrality::Centralities;
+use crate::zaphkiel::community::Communities;
+use crate::zaphkiel::cores::Cores;
+use crate::zaphkiel::error::Result;
+use crate::zaphkiel::exclusion::ExclusionReport;
+use crate::zaphkiel::ids::Id;
+use crate::zaphkiel::metadata::Metadata;
+use crate::zaphkiel::name_history::NameUse;
+use crate::zaphkiel::reciprocity::Reciprocity;
+use crate::zaphkiel::suspects::Suspects;
+use crate::zaphkiel::timeline::Timeline;
+
+/// A node of `graph.ron`, `graph2_sorted.ron` and `sorted_undirected_graph.ron`, keyed by user id
+/// with the display name as an attribute.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Node<E> {
+    pub name: Arc<str>,
+    pub edges: E,
+}
+
+/// `graph.ron`, the weight of every edge.
+pub type WeightedGraph = BTreeMap<Id, Node<BTreeMap<Id, f64>>>;
+/// `graph2_sorted.ron`, the edges of every node heaviest first.
+pub type Graph2Sorted = BTreeMap<Id, Node<Vec<(Id, Metadata)>>>;
+/// `sorted_undirected_graph.ron`, the nodes with the most neighbours first.
+pub type SortedUndirectedGraph = Vec<(Id, Node<Vec<Id>>)>;
+/// `friends.ron`, the people classified as friends of everyone, with their score.
+pub type FriendGraph = BTreeMap<Id, Vec<(Id, f64)>>;
+/// `names.ron`, every display name of everyone in the graph, with the current one first.
+pub type NameTable = BTreeMap<Id, (Arc<str>, Vec<NameUse>)>;
+
+/// Every serde output of a run, as written by the `ron` and `json` formats.
+///
+/// Only the three graphs are always there, the other files are `None` when they weren't written,
+/// like in the output directory of an older version. `timeline` is only there when `timeline` was
+/// run with the same output directory.
+///
+/// - `graph`: `graph.ron`.
+/// - `graph2_sorted`: `graph2_sorted.ron`.
+/// - `sorted_undirected_graph`: `sorted_undirected_graph.ron`.
+/// - `communities`: `communities.ron`.
+/// - `centrality`: `centrality.ron`.
+/// - `reciprocity`: `reciprocity.ron`.
+/// - `cores`: `cores.ron`.
+/// - `friends`: `friends.ron`.
+/// - `exclusions`: `exclusions.ron`.
+/// - `suspects`: `suspects.ron`.
+/// - `names`: `names.ron`.
+/// - `timeline`: `timeline.ron`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Results {
+    pub graph: WeightedGraph,
+    pub graph2_sorted: Graph2Sorted,
+    pub sorted_undirected_graph: SortedUndirectedGraph,
+    pub communities: Option<Communities<Id>>,
+    pub centrality: Option<Centralities<Id>>,
+    pub reciprocity: Option<Reciprocity>,
+    pub cores: Option<Cores<Id>>,
+    pub friends: Option<FriendGraph>,
+    pub exclusions: Option<ExclusionReport>,
+    pub suspects: Option<Suspects>,
+    pub names: Option<NameTable>,
+    pub timeline: Option<Timeline>,
+}
+
+impl Results {
+    /// Read the outputs written into `dir`, every file is read from its `.ron` version, or from
+    /// its `.json` one if there is no `.ron`. Files that are in neither format are `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::zaphkiel::error::Error::Io`] if one of the three graphs is missing, or
+    /// [`crate::zaphkiel::error::Error::Ron`] or [`crate::zaphkiel::error::Error::Json`] if a file
+    /// doesn't match its schema.
+    #[inline]
+    pub fn load(dir: &Path) -> Result<Self> {
+        Ok(Self {
+            graph: read(dir, "graph")?,
+            graph2_sorted: read(dir, "graph2_sorted")?,
+            sorted_undirected_graph: read(dir, "sorted_undirected_graph")?,
+            communities: read_optional(dir, "communities")?,
+            centrality: read_optional(dir, "centrality")?,
+            reciprocity: read_optional(dir, "reciprocity")?,
+            cores: read_optional(dir, "cores")?,
+            friends: read_optional(dir, "friends")?,
+            exclusions: read_optional(dir, "exclusions")?,
+            suspects: read_optional(dir, "suspects")?,
+            names: read_optional(dir, "names")?,
+            timeline: read_optional(dir, "timeline")?,
+        })
+    }
+
+    /// Write every output into `dir` as `<name>.<extension>` of `F`, the files [`Results::load`]
+    /// reads back. The outputs that are `None` are left alone.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::zaphkiel::error::Error::Io`] if a file can't be written.
+    #[inline]
+    pub fn write<F: SerdeFormat>(&self, dir: &Path) -> Result<()> {
+        write_document::<F, _>(dir, "graph", &self.graph)?;
+        write_document::<F, _>(dir, "graph2_sorted", &self.graph2_sorted)?;
+        write_document::<F, _>(
+            dir,
+            "sorted_undirected_graph",
+            &self.sorted_undirected_graph,
+        )?;
+        write_optional::<F, _>(dir, "communities", self.communities.as_ref())?;
+        write_optional::<F, _>(dir, "centrality", self.centrality.as_ref())?;
+        write_optional::<F, _>(dir, "reciprocity", self.reciprocity.as_ref())?;
+        write_optional::<F, _>(dir, "cores", self.cores.as_ref())?;
+        write_optional::<F, _>(dir, "friends", self.friends.as_ref())?;
+        write_optional::<F, _>(dir, "exclusions", self.exclusions.as_ref())?;
+        write_optional::<F, _>(dir, "suspects", self.suspects.as_ref())?;
+        write_optional::<F, _>(dir, "names", self.names.as_ref())?;
+        write_optional::<F, _>(dir, "timeline", self.timeline.as_ref())
+    }
+}
+
+/// A format that can hold any serde value, every output is written as `<name>.<EXTENSION>`.
+pub trait SerdeFormat {
+    const EXTENSION: &'static str;
+
+    /// # Errors
+    ///
+    /// Returns an error if `value` can't be serialized or written to `out`.
+    fn write<T: Serialize + ?Sized>(value: &T, out: impl Write) -> Result<()>;
+}
+
+/// Pretty printed RON, the default output format.
+pub struct RonFormat;
+
+impl SerdeFormat for RonFormat {
+    const EXTENSION: &'static str = "ron";
+
+    #[inline]
+    fn write<T: Serialize + ?Sized>(value: &T, out: impl Write) -> Result<()> {
+        ron::ser::to_writer_pretty(out, value, ron::ser::PrettyConfig::default())?;
+        Ok(())
+    }
+}
+
+/// Pretty printed JSON.
+pub struct JsonFormat;
+
+impl SerdeFormat for JsonFormat {
+    const EXTENSION: &'static str = "json";
+
+    #[inline]
+    fn write<T: Serialize + ?Sized>(value: &T, out: impl Write) -> Result<()> {
+        serde_json::to_writer_pretty(out, value)?;
+        Ok(())
+    }
+}
+
+/// Write `value` into `dir` as `<name>.<extension>` of `F`, replacing the file if it exists.
+///
+/// # Errors
+///
+/// Returns [`crate::zaphkiel::error::Error::Io`] if the file can't be written.
+#[inline]
+pub fn write_document<F: SerdeFormat, T: Serialize + ?Sized>(
+    dir: &Path,
+    name: &str,
+    value: &T,
+) -> Result<()> {
+    let path = dir.join(format!("{name}.{}", F::EXTENSION));
+    if std::fs::metadata(&path).is_ok() {
+        std::fs::remove_file(&path)?;
+    }
+    let mut out = BufWriter::new(File::create(path)?);
+    F::write(value, &mut out)?;
+    out.flush()?;
+
+    Ok(())
+}
+
+fn write_optional<F: SerdeFormat, T: Serialize>(
+    dir: &Path,
+    name: &str,
+    value: Option<&T>,
+) -> Result<()> {
+    value.map_or(Ok(()), |value| write_document::<F, _>(dir, name, value))
+}
+
+fn read<T: DeserializeOwned>(dir: &Path, name: &str) -> Result<T> {
+    let ron = dir.join(format!("{name}.ron"));
+    let json = dir.join(format!("{name}.json"));
+    if !ron.exists() && json.exists() {
+        return Ok(serde_json::from_reader(BufReader::new(File::open(json)?))?);
+    }
+
+    Ok(ron::de::from_reader(BufReader::new(File::open(ron)?))?)
+}
+
+fn read_optional<T: DeserializeOwned>(dir: &Path, name: &str) -> Result<Option<T>> {
+    let exists = |extension: &str| dir.join(format!("{name}.{extension}")).exists();
+    if !exists("ron") && !exists("json") {
+        return Ok(None);
+    }
+
+    read(dir, name).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    use crate::zaphkiel::centrality::{Centralities, Centrality};
+    use crate::zaphkiel::community::{Communities, Community};
+    use crate::zaphkiel::cores::{CircleCore, Cores};
+    use crate::zaphkiel::error::Error;
+    use crate::zaphkiel::exclusion::ExclusionReport;
+    use crate::zaphkiel::metadata::Metadata;
+    use crate::zaphkiel::reciprocity::{Attention, Reciprocity};
+    use crate::zaphkiel::results::{JsonFormat, Node, Results, RonFormat};
+    use crate::zaphkiel::suspects::Suspects;
+
+    fn node<E>(edges: E) -> Node<E> {
+        Node {
+            name: "A".into(),
+            edges,
+        }
+    }
+
+    #[test]
+    fn test_results_round_trip() {
+        let results = Results {
+            graph: BTreeMap::from([(
+                "usr_a".into(),
+                node(BTreeMap::from([("usr_b".into(), 2_f64)])),
+            )]),
+            graph2_sorted: BTreeMap::from([(
+                "usr_a".into(),
+                node(vec![(
+                    "usr_b".into(),
+                    Metadata::new(2, 2_f64, 3_f64, 2_f64),
+                )]),
+            )]),
+            sorted_undirected_graph: vec![("usr_a".into(), node(vec!["usr_b".into()]))],
+            communities: Some(Communities {
+                resolution: 1_f64,
+                modularity: 0_f64,
+                communities: vec![Community {
+                    name: "A's circle".to_string(),
+                    members: vec!["usr_a".into(), "usr_b".into()],
+                    internal_weight: 2_f64,
+                    cohesion: 1_f64,
+                    density: 1_f64,
+                }],
+            }),
+            centrality: Some(Centralities {
+                people: vec![Centrality {
+                    node: "usr_a".into(),
+                    betweenness: 0_f64,
+                    closeness: 1_f64,
+                    eigenvector: 1_f64,
+                    pagerank: 0.5_f64,
+                }],
+                connectors: vec![],
+            }),
+            reciprocity: Some(Reciprocity {
+                pairs: vec![],
+                attention: vec![Attention {
+                    user: "usr_a".into(),
+                    pairs: 1,
+                    given: 100_f64,
+                    received: 50_f64,
+                    balance: -50_f64,
+                }],
+            }),
+            cores: Some(Cores {
+                min_weight: 0_f64,
+                core_numbers: vec![("usr_a".into(), 1), ("usr_b".into(), 1)],
+                circles: vec![CircleCore {
+                    name: "A's circle".to_string(),
+                    k: 1,
+                    core: vec!["usr_a".into(), "usr_b".into()],
+                    cliques: vec![],
+                }],
+            }),
+            friends: Some(BTreeMap::from([(
+                "usr_a".into(),
+                vec![("usr_b".into(), 0.5_f64)],
+            )])),
+            exclusions: Some(ExclusionReport::default()),
+            suspects: Some(Suspects::default()),
+            names: Some(BTreeMap::new()),
+            timeline: None,
+        };
+
+        // through the same pretty printers as the outputs of a run
+        for (format, write) in [
+            (
+                "ron",
+                Results::write::<RonFormat> as fn(&Results, &Path) -> _,
+            ),
+            ("json", Results::write::<JsonFormat>),
+        ] {
+            let dir = std::env::temp_dir().join(format!(
+                "vrcx-insights-results-{format}-{}",
+                std::process::id()
+            ));
+            std::fs::create_dir_all(&dir).unwrap();
+            write(&results, &dir).unwrap();
+            let loaded = Results::load(&dir);
+            std::fs::remove_dir_all(&dir).unwrap();
+            let loaded = loaded.unwrap();
+            assert_eq!(loaded, results, "{format}");
+            let (_, metadata) = &loaded.graph2_sorted[&"usr_a".into()].edges[0];
+            assert!((metadata.percentile - 66.67).abs() < f64::EPSILON);
+        }
+
+        // an older run without cores, only the graphs have to be there
+        let dir = std::env::temp_dir().join(format!(
+            "vrcx-insights-results-partial-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let partial = Results {
+            cores: None,
+            ..results
+        };
+        partial.write::<RonFormat>(&dir).unwrap();
+        let loaded = Results::load(&dir);
+        std::fs::remove_file(dir.join("graph.ron")).unwrap();
+        let without_graph = Results::load(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.unwrap(), partial);
+        assert!(matches!(without_graph, Err(Error::Io(_))));
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+
+use chrono::Duration;
+use sqlx::types::chrono::{DateTime, Utc};
+
+use crate::zaphkiel::co_presence::Interval;
+use crate::zaphkiel::gamelog_join_leave::GamelogJoinLeave;
+use crate::zaphkiel::ids::Id;
+use crate::zaphkiel::join_leave_event::JoinLeaveEvent;
+use crate::zaphkiel::world_instance::WorldInstance;
+
+/// Longest a single session can last, anything longer is assumed to be a missing leave event and
+/// gets cut off here.
+pub const MAX_SESSION_HOURS: i64 = 24;
+
+/// How the end of a [`Session`] was found.
+///
+/// - `Leave`: there was a leave event for the join.
+/// - `LeaveOnly`: there was only a leave event, the join was recovered from its `time` column.
+/// - `Rejoin`: the user joined the same instance again without leaving, usually because VRChat
+///   crashed. The session ends at the last event seen in the instance before the rejoin.
+/// - `LastSeen`: there was no leave event at all, the session ends at the last event seen in the
+///   instance.
+/// - `Capped`: the session would have been longer than [`MAX_SESSION_HOURS`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SessionEnd {
+    Leave,
+    LeaveOnly,
+    Rejoin,
+    LastSeen,
+    Capped,
+}
+
+/// A stay of one user in one instance.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Session {
+    pub user: Id,
+    pub instance: WorldInstance,
+    pub joined_at: DateTime<Utc>,
+    pub left_at: DateTime<Utc>,
+    pub duration: Duration,
+    pub end: SessionEnd,
+}
+
+impl Session {
+    #[must_use]
+    #[inline]
+    pub fn new(
+        user: Id,
+        instance: WorldInstance,
+        joined_at: DateTime<Utc>,
+        left_at: DateTime<Utc>,
+        end: SessionEnd,
+    ) -> Self {
+        let (left_at, end) = if left_at - joined_at > Duration::hours(MAX_SESSION_HOURS) {
+            (
+                joined_at + Duration::hours(MAX_SESSION_HOURS),
+                SessionEnd::Capped,
+            )
+        } else {
+            (left_at, end)
+        };
+
+        Self {
+            user,
+            instance,
+            joined_at,
+            left_at,
+            duration: left_at - joined_at,
+            end,
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn interval(&self) -> Interval {
+        (self.joined_at, self.left_at)
+    }
+}
+
+/// Turn the event stream of `gamelog_join_leave` into sessions.
+///
+/// Rows can come from any number of instances and users, they're grouped by instance (see
+/// [`WorldInstance::get_prefix`]) and replayed in order of `created_at`. Rows without a user id or
+/// a location are ignored, and so are sessions that don't last any time at all.
+///
+/// Only the rows passed in are known, so the end of a session without a leave event is only as
+/// good as the other rows of its instance.
+#[must_use]
+#[inline]
+pub fn sessions_of(rows: &[GamelogJoinLeave]) -> Vec<Session> {
+    let mut instances: HashMap<String, Vec<&GamelogJoinLeave>> = HashMap::new();
+    for row in rows {
+        if let (Some(location), Some(_)) = (&row.location, &row.user_id) {
+            instances
+                .entry(location.get_prefix())
+                .or_default()
+                .push(row);
+        }
+    }
+
+    let mut sessions = instances
+        .into_values()
+        .flat_map(|mut rows| {
+            rows.sort_by_key(|row| (row.created_at, row.id));
+            replay(&rows)
+        })
+        .collect::<Vec<_>>();
+
+    sessions
+        .sort_by(|a, b| (a.joined_at, &a.user, a.left_at).cmp(&(b.joined_at, &b.user, b.left_at)));
+
+    sessions
+}
+
+/// Sessions grouped by user, as sorted [`Interval`]s.
+#[must_use]
+#[inline]
+pub fn intervals_by_user(sessions: &[Session]) -> HashMap<Id, Vec<Interval>> {
+    let mut ret: HashMap<Id, Vec<Interval>> = HashMap::new();
+    for session in sessions {
+        ret.entry(session.user.clone())
+            .or_default()
+            .push(session.interval());
+    }
+    for intervals in ret.values_mut() {
+        intervals.sort();
+    }
+    ret
+}
+
+/// Replay the sorted rows of a single instance.
+fn replay(rows: &[&GamelogJoinLeave]) -> Vec<Session> {
+    let mut sessions = vec![];
+    let mut open: HashMap<Id, (DateTime<Utc>, WorldInstance)> = HashMap::new();
+    let mut last_event: Option<DateTime<Utc>> = None;
+
+    for row in rows {
+        let (Some(user_id), Some(location)) = (&row.user_id, &row.location) else {
+            continue;
+        };
+        let user = Id::from(user_id);
+
+        match row.event {
+            JoinLeaveEvent::Join => {
+                if let Some((joined_at, instance)) = open.get(&user) {
+                    let ended_at = last_event.unwrap_or(*joined_at);
+                    if ended_at <= *joined_at {
+                        // the same join logged twice
+                        last_event = Some(row.created_at);
+                        continue;
+                    }
+                    sessions.push(Session::new(
+                        user.clone(),
+                        instance.clone(),
+                        *joined_at,
+                        ended_at,
+                        SessionEnd::Rejoin,
+                    ));
+                }
+                open.insert(user, (row.created_at, location.clone()));
+            }
+            JoinLeaveEvent::Leave => match open.remove(&user) {
+                Some((joined_at, instance)) => sessions.push(Session::new(
+                    user,
+                    instance,
+                    joined_at,
+                    row.created_at,
+                    SessionEnd::Leave,
+                )),
+                None => {
+                    let time = row.time.and_then(|it| i64::try_from(it).ok());
+                    if let Some(time) = time {
+                        sessions.push(Session::new(
+                            user,
+                            location.clone(),
+                            row.created_at - Duration::milliseconds(time),
+                            row.created_at,
+                            SessionEnd::LeaveOnly,
+                        ));
+                    }
+                }
+            },
+            JoinLeaveEvent::Other => {}
+        }
+
+        last_event = Some(row.created_at);
+    }
+
+    if let Some(last_event) = last_event {
+        for (user, (joined_at, instance)) in open {
+            sessions.push(Session::new(
+                user,
+                instance,
+                joined_at,
+                last_event,
+                SessionEnd::LastSeen,
+            ));
+        }
+    }
+
+    sessions.retain(|session| session.duration > Duration::zero());
+    sessions
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use sqlx::types::chrono::{TimeZone, Utc};
+
+    use crate::zaphkiel::gamelog_join_leave::GamelogJoinLeave;
+    use crate::zaphkiel::join_leave_event::JoinLeaveEvent;
+    use crate::zaphkiel::session::{sessions_of, SessionEnd, MAX_SESSION_HOURS};
+
+    fn row(minute: i64, event: JoinLeaveEvent, user: &str, time: Option<u64>) -> GamelogJoinLeave {
+        let mut row = GamelogJoinLeave::new();
+        row.created_at =
+            Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap() + Duration::minutes(minute);
+        row.event = event;
+        row.user_id = Some(user.into());
+        row.location = "wrld_a:1~region(eu)".parse().ok();
+        row.time = time;
+        row
+    }
+
+    #[test]
+    fn test_join_and_leave_make_a_session() {
+        let sessions = sessions_of(&[
+            row(0, JoinLeaveEvent::Join, "a", None),
+            row(30, JoinLeaveEvent::Leave, "a", Some(30 * 60 * 1000)),
+        ]);
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].duration, Duration::minutes(30));
+        assert_eq!(sessions[0].end, SessionEnd::Leave);
+    }
+
+    #[test]
+    fn test_leave_without_join_uses_time_column() {
+        let sessions = sessions_of(&[row(30, JoinLeaveEvent::Leave, "a", Some(10 * 60 * 1000))]);
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].duration, Duration::minutes(10));
+        assert_eq!(sessions[0].end, SessionEnd::LeaveOnly);
+    }
+
+    #[test]
+    fn test_duplicate_join_is_ignored() {
+        let sessions = sessions_of(&[
+            row(0, JoinLeaveEvent::Join, "a", None),
+            row(0, JoinLeaveEvent::Join, "a", None),
+            row(20, JoinLeaveEvent::Leave, "a", None),
+        ]);
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].duration, Duration::minutes(20));
+    }
+
+    #[test]
+    fn test_rejoin_after_crash_closes_at_last_event() {
+        let sessions = sessions_of(&[
+            row(0, JoinLeaveEvent::Join, "a", None),
+            row(10, JoinLeaveEvent::Join, "b", None),
+            row(60, JoinLeaveEvent::Join, "a", None),
+            row(90, JoinLeaveEvent::Leave, "a", None),
+        ]);
+        let a = sessions
+            .iter()
+            .filter(|it| it.user == "a".into())
+            .collect::<Vec<_>>();
+        assert_eq!(a.len(), 2);
+        assert_eq!(a[0].end, SessionEnd::Rejoin);
+        assert_eq!(a[0].duration, Duration::minutes(10));
+        assert_eq!(a[1].duration, Duration::minutes(30));
+        let b = sessions.iter().find(|it| it.user == "b".into()).unwrap();
+        assert_eq!(b.end, SessionEnd::LastSeen);
+        assert_eq!(b.duration, Duration::minutes(80));
+    }
+
+    #[test]
+    fn test_sessions_are_capped() {
+        let sessions = sessions_of(&[
+            row(0, JoinLeaveEvent::Join, "a", None),
+            row(60 * 48, JoinLeaveEvent::Leave, "a", None),
+        ]);
+        assert_eq!(sessions[0].end, SessionEnd::Capped);
+        assert_eq!(sessions[0].duration, Duration::hours(MAX_SESSION_HOURS));
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::zaphkiel::co_presence::CoPresenceMatrix;
+use crate::zaphkiel::ids::Id;
+use crate::zaphkiel::session::Session;
+
+/// Thresholds of [`Suspects::detect`].
+///
+/// - `alt_tolerance_secs`: how far apart the joins and the leaves of two sessions can be for them
+///   to count as the same visit.
+/// - `alt_min_sessions`: visits two accounts need to share before they can be alts.
+/// - `alt_min_share`: share of the sessions of *both* accounts that has to be shared, alts are
+///   (almost) never seen alone.
+/// - `bot_instances_per_day`: accounts seen in at least this many instances on a single day are
+///   bots, world hosts or cameras rather than people.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DetectionOptions {
+    pub alt_tolerance_secs: i64,
+    pub alt_min_sessions: u32,
+    pub alt_min_share: f64,
+    pub bot_instances_per_day: u32,
+}
+
+impl Default for DetectionOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            alt_tolerance_secs: 60,
+            alt_min_sessions: 3,
+            alt_min_share: 0.9,
+            bot_instances_per_day: 100,
+        }
+    }
+}
+
+/// Two accounts that join and leave together, `a` sorts before `b`.
+///
+/// - `shared`: number of visits with near-identical join and leave times.
+/// - `a_sessions`, `b_sessions`: number of sessions of each account.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AltPair {
+    pub a: Id,
+    pub b: Id,
+    pub shared: u32,
+    pub a_sessions: u32,
+    pub b_sessions: u32,
+}
+
+/// An account seen in too many instances to be a person.
+///
+/// - `days`: days the account has been seen on.
+/// - `peak_instances`: instances on its busiest day.
+/// - `mean_instances`: instances per day it has been seen on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bot {
+    pub id: Id,
+    pub days: u32,
+    pub peak_instances: u32,
+    pub mean_instances: f64,
+}
+
+/// Accounts that distort the graph, written to `suspects.ron`.
+///
+/// `alts` are sorted by `shared` and `bots` by `peak_instances`, highest first.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Suspects {
+    pub alts: Vec<AltPair>,
+    pub bots: Vec<Bot>,
+}
+
+impl Suspects {
+    #[must_use]
+    #[inline]
+    pub fn detect(sessions: &[Session], options: &DetectionOptions) -> Self {
+        Self {
+            alts: alt_pairs(sessions, options),
+            bots: bots(sessions, options),
+        }
+    }
+
+    /// Alt pairs joined into groups, each sorted by id, so chains of alts end up together.
+    #[must_use]
+    #[inline]
+    pub fn alt_groups(&self) -> Vec<BTreeSet<Id>> {
+        let mut groups: Vec<BTreeSet<Id>> = vec![];
+        for pair in &self.alts {
+            let touching = groups
+                .iter()
+                .enumerate()
+                .filter(|(_, group)| group.contains(&pair.a) || group.contains(&pair.b))
+                .map(|(idx, _)| idx)
+                .collect::<Vec<_>>();
+            let mut merged = BTreeSet::from([pair.a.clone(), pair.b.clone()]);
+            for idx in touching.into_iter().rev() {
+                merged.extend(groups.swap_remove(idx));
+            }
+            groups.push(merged);
+        }
+        groups.sort();
+        groups
+    }
+
+    /// Merge every alt group of `matrix` into a single node, see [`CoPresenceMatrix::merge`].
+    ///
+    /// Groups are merged into `keep` when it's part of them, and into the account with the most
+    /// sessions otherwise. Returns the account every alt was merged into.
+    #[inline]
+    pub fn merge_alts(&self, matrix: &mut CoPresenceMatrix, keep: Option<&Id>) -> HashMap<Id, Id> {
+        let mut merged = HashMap::new();
+        for group in self.alt_groups() {
+            let Some(main) = keep.filter(|keep| group.contains(*keep)).or_else(|| {
+                group
+                    .iter()
+                    .max_by_key(|id| (matrix.sightings(id), std::cmp::Reverse(*id)))
+            }) else {
+                continue;
+            };
+            let main = main.clone();
+            for alt in group.into_iter().filter(|it| *it != main) {
+                matrix.merge(&main, &alt);
+                merged.insert(alt, main.clone());
+            }
+        }
+        merged
+    }
+
+    /// Remove every bot from `matrix` except `keep`.
+    #[inline]
+    pub fn drop_bots(&self, matrix: &mut CoPresenceMatrix, keep: Option<&Id>) {
+        for bot in &self.bots {
+            if keep != Some(&bot.id) {
+                matrix.remove(&bot.id);
+            }
+        }
+    }
+}
+
+fn alt_pairs(sessions: &[Session], options: &DetectionOptions) -> Vec<AltPair> {
+    let tolerance = Duration::seconds(options.alt_tolerance_secs);
+
+    let mut counts: HashMap<&Id, u32> = HashMap::new();
+    let mut instances: HashMap<String, Vec<&Session>> = HashMap::new();
+    for session in sessions {
+        *counts.entry(&session.user).or_default() += 1;
+        instances
+            .entry(session.instance.get_prefix())
+            .or_default()
+            .push(session);
+    }
+
+    let mut shared: BTreeMap<(&Id, &Id), u32> = BTreeMap::new();
+    for mut sessions in instances.into_values() {
+        sessions.sort_by_key(|session| session.joined_at);
+        for (i, a) in sessions.iter().enumerate() {
+            // someone with several matching sessions in the window still only shares one visit
+            let mut matched = HashSet::new();
+            for b in sessions[i + 1..]
+                .iter()
+                .take_while(|b| b.joined_at - a.joined_at <= tolerance)
+            {
+                if a.user != b.user
+                    && (b.left_at - a.left_at).abs() <= tolerance
+                    && matched.insert(&b.user)
+                {
+                    let pair = if a.user < b.user {
+                        (&a.user, &b.user)
+                    } else {
+                        (&b.user, &a.user)
+                    };
+                    *shared.entry(pair).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    let mut alts = shared
+        .into_iter()
+        .filter(|(_, shared)| *shared >= options.alt_min_sessions)
+        .map(|((a, b), shared)| AltPair {
+            a: a.clone(),
+            b: b.clone(),
+            shared,
+            a_sessions: counts[a],
+            b_sessions: counts[b],
+        })
+        .filter(|pair| {
+            let share = |sessions: u32| f64::from(pair.shared) / f64::from(sessions);
+            share(pair.a_sessions) >= options.alt_min_share
+                && share(pair.b_sessions) >= options.alt_min_share
+        })
+        .collect::<Vec<_>>();
+    alts.sort_by(|x, y| y.shared.cmp(&x.shared).then_with(|| x.a.cmp(&y.a)));
+    alts
+}
+
+fn bots(sessions: &[Session], options: &DetectionOptions) -> Vec<Bot> {
+    let mut days: HashMap<&Id, HashMap<NaiveDate, HashSet<String>>> = HashMap::new();
+    for session in sessions {
+        days.entry(&session.user)
+            .or_default()
+            .entry(session.joined_at.date_naive())
+            .or_default()
+            .insert(session.instance.get_prefix());
+    }
+
+    let mut bots = days
+        .into_iter()
+        .filter_map(|(id, days)| {
+            let instances = days
+                .values()
+                .map(|it| u32::try_from(it.len()).unwrap_or(u32::MAX))
+                .collect::<Vec<_>>();
+            let peak_instances = instances.iter().copied().max().unwrap_or_default();
+            let days = u32::try_from(instances.len()).unwrap_or(u32::MAX);
+            (peak_instances >= options.bot_instances_per_day).then(|| Bot {
+                id: id.clone(),
+                days,
+                peak_instances,
+                mean_instances: instances.iter().copied().map(f64::from).sum::<f64>()
+                    / f64::from(days),
+            })
+        })
+        .collect::<Vec<_>>();
+    bots.sort_by(|a, b| {
+        b.peak_instances
+            .cmp(&a.peak_instances)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    bots
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::types::chrono::{DateTime, TimeZone, Utc};
+
+    use crate::zaphkiel::co_presence::CoPresenceMatrix;
+    use crate::zaphkiel::session::{Session, SessionEnd};
+    use crate::zaphkiel::suspects::{DetectionOptions, Suspects};
+
+    fn at(minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 1, 1, minute / 60, minute % 60, 0)
+            .unwrap()
+    }
+
+    fn session(user: &str, instance: &str, from: u32, to: u32) -> Session {
+        Session::new(
+            user.into(),
+            format!("wrld_a:{instance}").parse().unwrap(),
+            at(from),
+            at(to),
+            SessionEnd::Leave,
+        )
+    }
+
+    #[test]
+    fn test_alts_are_merged() {
+        let mut sessions = vec![];
+        for visit in 0..4 {
+            let instance = visit.to_string();
+            let from = visit * 60;
+            sessions.push(session("main", &instance, from, from + 30));
+            sessions.push(session("alt", &instance, from, from + 30));
+            // a friend who stays for a different time
+            sessions.push(session("friend", &instance, from + 10, from + 50));
+        }
+        let options = DetectionOptions::default();
+        let suspects = Suspects::detect(&sessions, &options);
+        assert_eq!(suspects.alts.len(), 1);
+        assert_eq!(suspects.alts[0].shared, 4);
+
+        let mut matrix = CoPresenceMatrix::from_sessions(&sessions);
+        let merged = suspects.merge_alts(&mut matrix, Some(&"main".into()));
+        assert_eq!(merged[&"alt".into()], "main".into());
+        assert!(matrix.get(&"alt".into()).is_none());
+        assert_eq!(matrix.others_of(&"main".into()).len(), 1);
+        assert_eq!(
+            matrix.others_of(&"friend".into())[&"main".into()].overlaps,
+            4
+        );
+    }
+
+    #[test]
+    fn test_bots_are_dropped() {
+        let mut sessions = vec![];
+        for instance in 0..5 {
+            let from = instance * 10;
+            sessions.push(session("bot", &instance.to_string(), from, from + 5));
+        }
+        sessions.push(session("person", "0", 0, 30));
+        let options = DetectionOptions {
+            bot_instances_per_day: 5,
+            ..DetectionOptions::default()
+        };
+        let suspects = Suspects::detect(&sessions, &options);
+        assert_eq!(suspects.bots.len(), 1);
+        assert_eq!(suspects.bots[0].peak_instances, 5);
+
+        let mut matrix = CoPresenceMatrix::from_sessions(&sessions);
+        suspects.drop_bots(&mut matrix, None);
+        assert_eq!(matrix.users().count(), 0);
+    }
+}
@@ -0,0 +1,231 @@
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+
+use crate::zaphkiel::co_presence::Interval;
+use crate::zaphkiel::error::{Error, Result};
+use crate::zaphkiel::session::{Session, MAX_SESSION_HOURS};
+
+/// The times every query keeps, sessions are cut down to the parts that fall inside.
+///
+/// - `since`: leave out everything before, in UTC.
+/// - `until`: leave out everything from then on, in UTC.
+/// - `weekdays`: days of the week to keep, every day if empty.
+/// - `hours`: hours of the day to keep, from the first included to the second excluded. a second
+///   hour smaller than the first wraps around midnight and belongs to the day it starts on, so
+///   `(20, 2)` on saturdays is saturday 8pm to sunday 2am. every hour if `None`.
+/// - `timezone`: timezone of `weekdays` and `hours`, UTC by default.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeFilter {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub weekdays: Vec<Weekday>,
+    pub hours: Option<(u32, u32)>,
+    pub timezone: Tz,
+}
+
+impl Default for TimeFilter {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            since: None,
+            until: None,
+            weekdays: vec![],
+            hours: None,
+            timezone: Tz::UTC,
+        }
+    }
+}
+
+impl TimeFilter {
+    /// Whether the filter keeps everything.
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.since.is_none() && self.until.is_none() && !self.cuts_days()
+    }
+
+    fn cuts_days(&self) -> bool {
+        !self.weekdays.is_empty() || self.hours.is_some()
+    }
+
+    /// Bounds on `created_at` for the queries, with room for the sessions that start before
+    /// `since` or end after `until`. Timestamps are compared as strings, like VRCX writes them.
+    #[must_use]
+    #[inline]
+    pub fn created_at_range(&self) -> (String, String) {
+        let margin = Duration::hours(MAX_SESSION_HOURS);
+        let format = |at: DateTime<Utc>| at.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
+        (
+            self.since
+                .map_or_else(String::new, |since| format(since - margin)),
+            self.until
+                .map_or_else(|| "9999".to_string(), |until| format(until + margin)),
+        )
+    }
+
+    /// The parts of `session` the filter keeps, in order.
+    #[must_use]
+    #[inline]
+    pub fn clip(&self, session: &Session) -> Vec<Session> {
+        self.ranges(session.joined_at, session.left_at)
+            .into_iter()
+            .map(|(joined_at, left_at)| Session {
+                joined_at,
+                left_at,
+                duration: left_at - joined_at,
+                ..session.clone()
+            })
+            .collect()
+    }
+
+    /// Whether the filter keeps the instant `at`.
+    #[must_use]
+    #[inline]
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        !self.ranges(at, at + Duration::seconds(1)).is_empty()
+    }
+
+    /// The parts of `start..end` the filter keeps, in order.
+    fn ranges(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<Interval> {
+        let start = self.since.map_or(start, |since| since.max(start));
+        let end = self.until.map_or(end, |until| until.min(end));
+        if start >= end {
+            return vec![];
+        }
+        if !self.cuts_days() {
+            return vec![(start, end)];
+        }
+
+        let mut ranges: Vec<Interval> = vec![];
+        // the hours of the day before can run past midnight
+        let first = start.with_timezone(&self.timezone).date_naive() - Duration::days(1);
+        let last = end.with_timezone(&self.timezone).date_naive();
+        for day in first.iter_days().take_while(|day| *day <= last) {
+            if !self.weekdays.is_empty() && !self.weekdays.contains(&day.weekday()) {
+                continue;
+            }
+            let (from, to) = match self.hours {
+                Some((from, to)) if to > from => (from, to),
+                Some((from, to)) => (from, to + 24),
+                None => (0, 24),
+            };
+            let from = self.local(day, from).max(start);
+            let to = self.local(day, to).min(end);
+            if from >= to {
+                continue;
+            }
+            match ranges.last_mut() {
+                // back to back days are still a single visit
+                Some(last) if last.1 == from => last.1 = to,
+                _ => ranges.push((from, to)),
+            }
+        }
+
+        ranges
+    }
+
+    /// `hour` o'clock on `day` in the timezone of the filter, hours past 24 are on the next days.
+    fn local(&self, day: NaiveDate, hour: u32) -> DateTime<Utc> {
+        let day = day + Duration::days(i64::from(hour / 24));
+        let naive =
+            day.and_time(NaiveTime::from_hms_opt(hour % 24, 0, 0).unwrap_or(NaiveTime::MIN));
+        self.timezone
+            .from_local_datetime(&naive)
+            .earliest()
+            // skipped by a DST change, the clock jumps over that hour
+            .unwrap_or_else(|| self.timezone.from_utc_datetime(&naive))
+            .with_timezone(&Utc)
+    }
+}
+
+/// Parse an instant for `since` and `until`: an RFC 3339 timestamp, a `YYYY-MM-DD` date (midnight
+/// UTC) or a number of days before `now` like `90d`.
+///
+/// # Errors
+///
+/// Returns [`Error::Config`] if `value` is none of those.
+#[inline]
+pub fn parse_instant(value: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+    if let Some(days) = value.strip_suffix('d') {
+        if let Ok(days) = days.parse::<i64>() {
+            return Ok(now - Duration::days(days));
+        }
+    }
+    if let Ok(day) = value.parse::<NaiveDate>() {
+        return Ok(day.and_time(NaiveTime::MIN).and_utc());
+    }
+    value
+        .parse::<DateTime<Utc>>()
+        .map_err(|_| Error::Config(format!("invalid date: {value}, expected 2023-01-31 or 90d")))
+}
+
+/// Parse hours of the day like `20-2`.
+///
+/// # Errors
+///
+/// Returns [`Error::Config`] if `value` isn't two hours from 0 to 24 separated by a dash.
+#[inline]
+pub fn parse_hours(value: &str) -> Result<(u32, u32)> {
+    let invalid = || Error::Config(format!("invalid hours: {value}, expected 20-2"));
+    let (from, to) = value.split_once('-').ok_or_else(invalid)?;
+    let hour = |it: &str| {
+        it.trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|it| *it <= 24)
+            .ok_or_else(invalid)
+    };
+
+    Ok((hour(from)?, hour(to)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc, Weekday};
+
+    use crate::zaphkiel::session::{Session, SessionEnd};
+    use crate::zaphkiel::time_filter::TimeFilter;
+
+    #[test]
+    fn test_weekend_evenings_in_a_timezone() {
+        let session = |start, end| {
+            Session::new(
+                "usr_a".into(),
+                "wrld_a:1".try_into().unwrap(),
+                start,
+                end,
+                SessionEnd::Leave,
+            )
+        };
+        let filter = TimeFilter {
+            weekdays: vec![Weekday::Sat, Weekday::Sun],
+            hours: Some((20, 2)),
+            timezone: chrono_tz::Europe::Paris,
+            ..TimeFilter::default()
+        };
+
+        // saturday 7pm to sunday 4am in Paris, kept from 8pm to 2am
+        let saturday = session(
+            Utc.with_ymd_and_hms(2023, 1, 7, 18, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2023, 1, 8, 3, 0, 0).unwrap(),
+        );
+        let pieces = filter.clip(&saturday);
+        assert_eq!(pieces.len(), 1);
+        assert_eq!(
+            pieces[0].joined_at,
+            Utc.with_ymd_and_hms(2023, 1, 7, 19, 0, 0).unwrap()
+        );
+        assert_eq!(pieces[0].duration, Duration::hours(6));
+
+        // friday night runs past midnight into saturday but belongs to friday
+        let friday = session(
+            Utc.with_ymd_and_hms(2023, 1, 6, 20, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2023, 1, 7, 0, 30, 0).unwrap(),
+        );
+        assert!(filter.clip(&friday).is_empty());
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use chrono::{Datelike, Duration, Months, NaiveTime};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+
+use crate::zaphkiel::co_presence::CoPresenceMatrix;
+use crate::zaphkiel::error::Error;
+use crate::zaphkiel::ids::Id;
+use crate::zaphkiel::metadata::metadata_of;
+use crate::zaphkiel::session::Session;
+use crate::zaphkiel::weight::WeightMode;
+
+/// Length of a window, or of the step between two windows. Weeks start on Monday and months on
+/// the 1st, in UTC. Shorter periods come first.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+pub enum Period {
+    Week,
+    #[default]
+    Month,
+}
+
+impl Period {
+    /// Start of the period `at` falls in.
+    #[must_use]
+    #[inline]
+    pub fn floor(self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let day = at.date_naive();
+        let day = match self {
+            Self::Week => day - Duration::days(i64::from(day.weekday().num_days_from_monday())),
+            Self::Month => day.with_day(1).unwrap_or(day),
+        };
+        day.and_time(NaiveTime::MIN).and_utc()
+    }
+
+    /// `at` moved one period forward.
+    #[must_use]
+    #[inline]
+    pub fn next(self, at: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Self::Week => at + Duration::weeks(1),
+            Self::Month => at
+                .checked_add_months(Months::new(1))
+                .unwrap_or(DateTime::<Utc>::MAX_UTC),
+        }
+    }
+}
+
+impl FromStr for Period {
+    type Err = Error;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "week" | "weekly" => Ok(Self::Week),
+            "month" | "monthly" => Ok(Self::Month),
+            _ => Err(Error::Config(format!("unknown period: {s}"))),
+        }
+    }
+}
+
+impl Display for Period {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Week => "week",
+            Self::Month => "month",
+        })
+    }
+}
+
+/// How the history is cut into windows.
+///
+/// - `period`: length of every window.
+/// - `step`: time between the starts of two windows, the same as `period` for calendar windows,
+///   shorter for sliding ones.
+/// - `mode`: how the co-presence of a window is turned into a weight.
+/// - `circle_share`: percentage of someone's weight in a window a person needs to be in their
+///   circle, see [`Circle`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(clippy::module_name_repetitions)]
+pub struct TimelineOptions {
+    pub period: Period,
+    pub step: Period,
+    pub mode: WeightMode,
+    pub circle_share: f64,
+}
+
+impl Default for TimelineOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            period: Period::Month,
+            step: Period::Month,
+            mode: WeightMode::Count,
+            circle_share: 5_f64,
+        }
+    }
+}
+
+/// A slice of the history, from `start` included to `end` excluded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Window {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl Window {
+    /// The part of every session that falls in the window.
+    #[must_use]
+    #[inline]
+    pub fn clip(&self, sessions: &[Session]) -> Vec<Session> {
+        sessions
+            .iter()
+            .filter(|session| session.joined_at < self.end && session.left_at > self.start)
+            .map(|session| {
+                let joined_at = session.joined_at.max(self.start);
+                let left_at = session.left_at.min(self.end);
+                Session {
+                    joined_at,
+                    left_at,
+                    duration: left_at - joined_at,
+                    ..session.clone()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Windows covering every session, windows start on a `step` boundary and last a `period`.
+///
+/// A `step` longer than `period` leaves gaps between the windows.
+#[must_use]
+#[inline]
+pub fn windows_of(sessions: &[Session], period: Period, step: Period) -> Vec<Window> {
+    let (Some(first), Some(last)) = (
+        sessions.iter().map(|it| it.joined_at).min(),
+        sessions.iter().map(|it| it.left_at).max(),
+    ) else {
+        return vec![];
+    };
+
+    let mut windows = vec![];
+    let mut start = step.floor(first);
+    while start < last {
+        windows.push(Window {
+            start,
+            end: period.next(start),
+        });
+        start = step.next(start);
+    }
+
+    windows
+}
+
+/// The weight of a pair of users in every window, `a` is the smaller id.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PairSeries {
+    pub a: Id,
+    pub b: Id,
+    pub weights: Vec<f64>,
+}
+
+/// The circle of a user in one window: the people who got at least `circle_share` percent of their
+/// weight, and who came and went since the window before. Nobody comes or goes in the first
+/// window.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircleChange {
+    pub members: BTreeSet<Id>,
+    pub entered: BTreeSet<Id>,
+    pub left: BTreeSet<Id>,
+}
+
+/// How the circle of `user` changed over the windows, one change per window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Circle {
+    pub user: Id,
+    pub changes: Vec<CircleChange>,
+}
+
+/// The graph over time, see [`Timeline::build`].
+///
+/// - `weight`: the weight mode of `series`.
+/// - `windows`: the windows, oldest first.
+/// - `series`: the weight of every pair in every window, in the order of `windows`.
+/// - `circle`: how the circle of the user it was built for changed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Timeline {
+    pub weight: WeightMode,
+    pub windows: Vec<Window>,
+    pub series: Vec<PairSeries>,
+    pub circle: Option<Circle>,
+}
+
+impl Timeline {
+    /// Cut `sessions` into windows and build the co-presence of every window, following the
+    /// pairs of `pairs` and the circle of `user`.
+    ///
+    /// Pairs that were never seen together in any window are left out.
+    #[must_use]
+    #[inline]
+    pub fn build<'a>(
+        sessions: &[Session],
+        pairs: impl IntoIterator<Item = (&'a Id, &'a Id)>,
+        user: Option<&Id>,
+        options: &TimelineOptions,
+    ) -> Self {
+        let windows = windows_of(sessions, options.period, options.step);
+        let pairs = pairs
+            .into_iter()
+            .filter(|(a, b)| a != b)
+            .map(|(a, b)| if a < b { (a, b) } else { (b, a) })
+            .collect::<BTreeSet<_>>();
+
+        let mut series = pairs
+            .iter()
+            .map(|(a, b)| PairSeries {
+                a: (*a).clone(),
+                b: (*b).clone(),
+                weights: Vec::with_capacity(windows.len()),
+            })
+            .collect::<Vec<_>>();
+        let mut changes: Vec<CircleChange> = vec![];
+        for window in &windows {
+            let matrix = CoPresenceMatrix::from_sessions(&window.clip(sessions));
+            for pair in &mut series {
+                let weight = matrix
+                    .get(&pair.a)
+                    .and_then(|others| others.get(&pair.b))
+                    .map_or(0_f64, |co_presence| options.mode.weight(co_presence));
+                pair.weights.push(weight);
+            }
+
+            if let Some(user) = user {
+                let others = matrix.others_of(user);
+                let members = metadata_of(&others, options.mode)
+                    .into_iter()
+                    .filter(|(_, metadata)| metadata.percentage >= options.circle_share)
+                    .map(|(other, _)| other.clone())
+                    .collect::<BTreeSet<_>>();
+                let before = changes.last().map(|it| &it.members);
+                let entered = members
+                    .iter()
+                    .filter(|it| before.is_some_and(|before| !before.contains(*it)))
+                    .cloned()
+                    .collect();
+                let left = before
+                    .map(|before| before.difference(&members).cloned().collect())
+                    .unwrap_or_default();
+                changes.push(CircleChange {
+                    members,
+                    entered,
+                    left,
+                });
+            }
+        }
+        series.retain(|pair| pair.weights.iter().any(|it| *it > 0_f64));
+
+        Self {
+            weight: options.mode,
+            windows,
+            series,
+            circle: user.map(|user| Circle {
+                user: user.clone(),
+                changes,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use crate::zaphkiel::ids::Id;
+    use crate::zaphkiel::session::{Session, SessionEnd};
+    use crate::zaphkiel::timeline::{Period, Timeline, TimelineOptions};
+
+    #[test]
+    fn test_circle_changes_between_months() {
+        let session = |user: &str, month: u32, instance: &str| {
+            let start = Utc.with_ymd_and_hms(2023, month, 10, 20, 0, 0).unwrap();
+            Session::new(
+                user.into(),
+                instance.try_into().unwrap(),
+                start,
+                start + Duration::hours(1),
+                SessionEnd::Leave,
+            )
+        };
+        // b is around in january, c in february, and a is in both
+        let sessions = [
+            session("a", 1, "wrld_a:1"),
+            session("b", 1, "wrld_a:1"),
+            session("a", 2, "wrld_a:2"),
+            session("c", 2, "wrld_a:2"),
+        ];
+        let (a, b, c) = (Id::from("a"), Id::from("b"), Id::from("c"));
+        let timeline = Timeline::build(
+            &sessions,
+            [(&a, &b), (&c, &a)],
+            Some(&a),
+            &TimelineOptions::default(),
+        );
+
+        assert_eq!(timeline.windows.len(), 2);
+        assert_eq!(
+            timeline.windows[1].start,
+            Period::Month.floor(sessions[2].joined_at)
+        );
+        assert_eq!(timeline.series[0].weights, vec![1_f64, 0_f64]);
+        assert_eq!(timeline.series[1].a, a);
+        assert_eq!(timeline.series[1].weights, vec![0_f64, 1_f64]);
+
+        let changes = &timeline.circle.unwrap().changes;
+        assert!(changes[0].entered.is_empty());
+        assert_eq!(changes[1].entered, [c].into());
+        assert_eq!(changes[1].left, [b].into());
+    }
+}
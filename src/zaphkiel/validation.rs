@@ -0,0 +1,118 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::BuildHasher;
+
+use crate::zaphkiel::friendship::{Confusion, FriendshipClassifier, PairStats};
+use crate::zaphkiel::ids::Id;
+
+/// How the inferred friends of a user compare to their actual friend list.
+///
+/// - `confusion`: guesses of the classifier for everyone the user has been seen with, friends who
+///   have never been seen can't be guessed and are left out.
+/// - `strangers`: people who aren't friends but were classified as such, most time together first.
+/// - `missed_friends`: friends who have been seen but weren't classified as such, most time
+///   together first.
+/// - `unseen_friends`: friends who have never been seen with the user.
+/// - `former_friends`: people who were unfriended but have been seen with the user, most time
+///   together first.
+#[derive(Debug, Clone, Default)]
+pub struct Validation {
+    pub confusion: Confusion,
+    pub strangers: Vec<(Id, PairStats)>,
+    pub missed_friends: Vec<(Id, PairStats)>,
+    pub unseen_friends: Vec<Id>,
+    pub former_friends: Vec<(Id, PairStats)>,
+}
+
+impl Validation {
+    /// `stats` are the [`PairStats`] of the user with everyone they've been seen with.
+    #[must_use]
+    #[inline]
+    pub fn new<S, T, U>(
+        stats: &HashMap<Id, PairStats, S>,
+        friends: &HashSet<Id, T>,
+        former_friends: &HashSet<Id, U>,
+        classifier: &dyn FriendshipClassifier,
+    ) -> Self
+    where
+        S: BuildHasher,
+        T: BuildHasher,
+        U: BuildHasher,
+    {
+        let samples = stats
+            .iter()
+            .map(|(other, stats)| (*stats, friends.contains(other)))
+            .collect::<Vec<_>>();
+        let confusion = Confusion::of(&samples, |stats| classifier.is_friend(stats));
+
+        let mut strangers = vec![];
+        let mut missed_friends = vec![];
+        let mut former = vec![];
+        for (other, stats) in stats {
+            match (friends.contains(other), classifier.is_friend(stats)) {
+                (false, true) => strangers.push((other.clone(), *stats)),
+                (true, false) => missed_friends.push((other.clone(), *stats)),
+                _ => {}
+            }
+            if former_friends.contains(other) {
+                former.push((other.clone(), *stats));
+            }
+        }
+        for list in [&mut strangers, &mut missed_friends, &mut former] {
+            list.sort_by(|(a_id, a), (b_id, b)| {
+                b.hours.total_cmp(&a.hours).then_with(|| a_id.cmp(b_id))
+            });
+        }
+
+        let mut unseen_friends = friends
+            .iter()
+            .filter(|friend| !stats.contains_key(*friend))
+            .cloned()
+            .collect::<Vec<_>>();
+        unseen_friends.sort();
+
+        Self {
+            confusion,
+            strangers,
+            missed_friends,
+            unseen_friends,
+            former_friends: former,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use crate::zaphkiel::friendship::{PairStats, ZScore};
+    use crate::zaphkiel::ids::Id;
+    use crate::zaphkiel::validation::Validation;
+
+    #[test]
+    fn test_validation_buckets() {
+        let stats = |hours: f64, z_score: f64| PairStats {
+            hours,
+            z_score,
+            ..PairStats::default()
+        };
+        let stats: HashMap<Id, PairStats> = [
+            ("friend".into(), stats(10_f64, 2_f64)),
+            ("stranger".into(), stats(8_f64, 2_f64)),
+            ("missed".into(), stats(0.1, -1_f64)),
+            ("nobody".into(), stats(0.1, -1_f64)),
+        ]
+        .into();
+        let friends: HashSet<Id> = ["friend".into(), "missed".into(), "unseen".into()].into();
+        let former: HashSet<Id> = ["nobody".into()].into();
+
+        let validation = Validation::new(&stats, &friends, &former, &ZScore::default());
+        assert_eq!(validation.confusion.true_positives, 1);
+        assert_eq!(validation.confusion.false_positives, 1);
+        assert_eq!(validation.confusion.false_negatives, 1);
+        assert_eq!(validation.confusion.true_negatives, 1);
+        assert_eq!(validation.strangers[0].0, "stranger".into());
+        assert_eq!(validation.missed_friends[0].0, "missed".into());
+        assert_eq!(validation.unseen_friends, vec!["unseen".into()]);
+        assert_eq!(validation.former_friends[0].0, "nobody".into());
+    }
+}
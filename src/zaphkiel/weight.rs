@@ -0,0 +1,98 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::zaphkiel::co_presence::CoPresence;
+use crate::zaphkiel::error::Error;
+
+/// How a [`CoPresence`] is turned into the weight of an edge.
+///
+/// - `Count`: number of overlapping stays, a 30-second crossing counts as much as a four-hour
+///   hangout.
+/// - `Time`: hours spent together.
+/// - `Days`: number of distinct days spent together.
+/// - `LogCount`: `ln(1 + count)`, so a few very frequent pairs don't drown out everyone else.
+/// - `LogTime`: `ln(1 + hours)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[allow(clippy::module_name_repetitions)]
+pub enum WeightMode {
+    #[default]
+    Count,
+    Time,
+    Days,
+    LogCount,
+    LogTime,
+}
+
+impl WeightMode {
+    #[must_use]
+    #[inline]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn weight(self, co_presence: &CoPresence) -> f64 {
+        let count = f64::from(co_presence.overlaps);
+        let hours = co_presence.seconds as f64 / 3600_f64;
+        let days = f64::from(co_presence.distinct_days());
+
+        match self {
+            Self::Count => count,
+            Self::Time => hours,
+            Self::Days => days,
+            Self::LogCount => count.ln_1p(),
+            Self::LogTime => hours.ln_1p(),
+        }
+    }
+}
+
+impl FromStr for WeightMode {
+    type Err = Error;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace(['-', '_'], "").as_str() {
+            "count" => Ok(Self::Count),
+            "time" | "hours" => Ok(Self::Time),
+            "days" => Ok(Self::Days),
+            "logcount" => Ok(Self::LogCount),
+            "logtime" | "loghours" => Ok(Self::LogTime),
+            _ => Err(Error::Config(format!("unknown weight mode: {s}"))),
+        }
+    }
+}
+
+impl Display for WeightMode {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Count => "count",
+            Self::Time => "time",
+            Self::Days => "days",
+            Self::LogCount => "log-count",
+            Self::LogTime => "log-time",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::zaphkiel::co_presence::CoPresence;
+    use crate::zaphkiel::weight::WeightMode;
+
+    #[test]
+    fn test_weight_modes() {
+        let co_presence = CoPresence {
+            overlaps: 3,
+            seconds: 2 * 3600,
+            days: ["2023-01-01".parse().unwrap(), "2023-01-02".parse().unwrap()].into(),
+            crowd_seconds: 3600_f64,
+        };
+        assert!((WeightMode::Count.weight(&co_presence) - 3.0).abs() < f64::EPSILON);
+        assert!((WeightMode::Time.weight(&co_presence) - 2.0).abs() < f64::EPSILON);
+        assert!((WeightMode::Days.weight(&co_presence) - 2.0).abs() < f64::EPSILON);
+        assert!((WeightMode::LogTime.weight(&co_presence) - 3_f64.ln()).abs() < 1e-9);
+        assert_eq!(
+            "log-count".parse::<WeightMode>().unwrap(),
+            WeightMode::LogCount
+        );
+    }
+}
@@ -41,7 +41,7 @@ impl WorldInstance {
 /// - `InvalidWorldId`: The world id is invalid.
 /// - `InvalidInstanceId`: The instance id is invalid.
 /// - `InvalidOptionalField`: The optional field is invalid.
-/// - `Other`: Other errors.
+/// - `Other`: Other errors, like an unknown optional field.
 #[derive(Debug, Clone, sqlx::Type, Default, PartialEq, Eq)]
 #[allow(clippy::module_name_repetitions)] // I want it like that ~kat
 pub enum WorldInstanceParseError {
@@ -94,11 +94,23 @@ impl FromStr for WorldInstance {
                 "nonce" => ret.nonce = Some(value),
                 "hidden" => ret.hidden = Some(value),
                 "private" => ret.private = Some(value),
-                "region" => ret.region = Some(value.into()),
+                "region" => {
+                    ret.region = Some(
+                        value
+                            .parse()
+                            .map_err(|_| WorldInstanceParseError::InvalidOptionalField)?,
+                    );
+                }
                 "friends" => ret.friends = Some(value),
                 "group" => ret.group = Some(value),
-                "groupAccessType" => ret.group_access_type = Some(value.into()),
-                _ => panic!("Unknown key: {key}, {part}"),
+                "groupAccessType" => {
+                    ret.group_access_type = Some(
+                        value
+                            .parse()
+                            .map_err(|_| WorldInstanceParseError::InvalidOptionalField)?,
+                    );
+                }
+                _ => return Err(WorldInstanceParseError::Other),
             }
         }
 
@@ -106,19 +118,21 @@ impl FromStr for WorldInstance {
     }
 }
 
-#[allow(clippy::fallible_impl_from)] // I want it like that ~kat
-impl From<&str> for WorldInstance {
+impl TryFrom<&str> for WorldInstance {
+    type Error = WorldInstanceParseError;
+
     #[inline]
-    fn from(s: &str) -> Self {
-        Self::from_str(s).unwrap()
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        Self::from_str(s)
     }
 }
 
-#[allow(clippy::fallible_impl_from)] // I want it like that ~kat
-impl From<String> for WorldInstance {
+impl TryFrom<String> for WorldInstance {
+    type Error = WorldInstanceParseError;
+
     #[inline]
-    fn from(s: String) -> Self {
-        Self::from_str(&s).unwrap()
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::from_str(&s)
     }
 }
 
@@ -185,7 +199,6 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "unknown key in world instance string")]
     fn test_parse_world_instance_unknown_key() {
         let world_instance_str = "world_id:instance_id~unknown_key(value)";
         let actual_result = WorldInstance::from_str(world_instance_str);
@@ -193,6 +206,16 @@ mod tests {
         assert_eq!(actual_result.unwrap_err(), WorldInstanceParseError::Other);
     }
 
+    #[test]
+    fn test_parse_world_instance_invalid_region() {
+        let world_instance_str = "world_id:instance_id~region(mars)";
+        let actual_result = WorldInstance::from_str(world_instance_str);
+        assert_eq!(
+            actual_result.unwrap_err(),
+            WorldInstanceParseError::InvalidOptionalField
+        );
+    }
+
     #[test]
     fn test_from_str_for_world_instance_empty_input() {
         let world_instance_str = "";
@@ -205,7 +228,8 @@ mod tests {
     fn test_from_str_for_world_instance_from_string() {
         let world_instance_str = "world_id:instance_id~region(EU)";
         let expected_world_instance = world_instance_data();
-        let actual_world_instance = WorldInstance::from(world_instance_str.to_string());
+        let actual_world_instance =
+            WorldInstance::try_from(world_instance_str.to_string()).unwrap();
         assert_eq!(actual_world_instance, expected_world_instance);
     }
 
@@ -213,7 +237,7 @@ mod tests {
     fn test_from_str_for_world_instance_from_str() {
         let world_instance_str = "world_id:instance_id~region(EU)";
         let expected_world_instance = world_instance_data();
-        let actual_world_instance = WorldInstance::from(world_instance_str);
+        let actual_world_instance = WorldInstance::try_from(world_instance_str).unwrap();
         assert_eq!(actual_world_instance, expected_world_instance);
     }
 }
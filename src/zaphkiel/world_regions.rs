@@ -1,5 +1,7 @@
 use std::str::FromStr;
 
+use crate::zaphkiel::error::ParseError;
+
 #[derive(Debug, Clone, Copy, sqlx::Type, Default, PartialEq, Eq, Hash)]
 pub enum Regions {
     #[default]
@@ -11,36 +13,38 @@ pub enum Regions {
     Japan,
 }
 
-impl From<&str> for Regions {
-    #[inline]
-    fn from(value: &str) -> Self {
-        let value = value.to_lowercase();
-        Self::from(value)
-    }
-}
+impl TryFrom<&str> for Regions {
+    type Error = ParseError;
 
-#[allow(clippy::fallible_impl_from)] // ~kat
-impl From<String> for Regions {
     #[inline]
-    fn from(value: String) -> Self {
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
         let value = value.to_lowercase();
         match value.as_str() {
-            "uswest" | "usw" | "us w" | "us_w" | "uw" => Self::USWest,
-            "us" => Self::US,
-            "useast" | "use" | "us e" | "us_e" | "ue" => Self::USEast,
-            "europe" | "eu" => Self::Europe,
-            "japan" | "jp" => Self::Japan,
+            "uswest" | "usw" | "us w" | "us_w" | "uw" => Ok(Self::USWest),
+            "us" => Ok(Self::US),
+            "useast" | "use" | "us e" | "us_e" | "ue" => Ok(Self::USEast),
+            "europe" | "eu" => Ok(Self::Europe),
+            "japan" | "jp" => Ok(Self::Japan),
 
-            _ => panic!("Unknown region: {value}"),
+            _ => Err(ParseError::UnknownRegion(value)),
         }
     }
 }
 
+impl TryFrom<String> for Regions {
+    type Error = ParseError;
+
+    #[inline]
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from(value.as_str())
+    }
+}
+
 impl FromStr for Regions {
-    type Err = std::string::ParseError;
+    type Err = ParseError;
 
     #[inline]
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self::from(s))
+        Self::try_from(s)
     }
 }